@@ -0,0 +1,2 @@
+ALTER TABLE segments DROP COLUMN category;
+DROP TYPE segment_category;
@@ -0,0 +1,15 @@
+-- names follow SponsorBlock, so that existing clients can share them
+CREATE TYPE segment_category AS ENUM (
+  'sponsor',
+  'intro',
+  'outro',
+  'selfpromo',
+  'interaction',
+  'preview',
+  'filler',
+  'music_offtopic'
+);
+
+-- segments submitted before categories existed are all sponsors
+ALTER TABLE segments ADD COLUMN category segment_category NOT NULL DEFAULT 'sponsor';
+ALTER TABLE segments ALTER COLUMN category DROP DEFAULT;
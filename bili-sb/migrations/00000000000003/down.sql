@@ -0,0 +1,6 @@
+ALTER TABLE segments DROP CONSTRAINT segments_range_check;
+-- existing labels and highlights would violate it
+ALTER TABLE segments ADD CONSTRAINT segments_check CHECK("start" < "end") NOT VALID;
+
+ALTER TABLE segments DROP COLUMN action_type;
+DROP TYPE action_type;
@@ -0,0 +1,14 @@
+CREATE TYPE action_type AS ENUM ('skip', 'mute', 'full', 'poi');
+
+ALTER TABLE segments ADD COLUMN action_type action_type NOT NULL DEFAULT 'skip';
+ALTER TABLE segments ALTER COLUMN action_type DROP DEFAULT;
+
+-- full video labels have no range, and point of interests are a single timestamp
+ALTER TABLE segments DROP CONSTRAINT segments_check;
+ALTER TABLE segments ADD CONSTRAINT segments_range_check CHECK(
+  CASE action_type
+    WHEN 'full' THEN "start" = 0 AND "end" = 0
+    WHEN 'poi'  THEN "start" = "end"
+    ELSE "start" < "end"
+  END
+);
@@ -0,0 +1,2 @@
+DROP INDEX idx_videos_bvid_hash;
+ALTER TABLE videos DROP COLUMN bvid_hash;
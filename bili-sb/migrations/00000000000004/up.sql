@@ -0,0 +1,5 @@
+-- SHA-256 of bvid in lowercase hex, for hash prefix lookups,
+-- rows inserted before this column are filled on server startup
+ALTER TABLE videos ADD COLUMN bvid_hash VARCHAR(64);
+
+CREATE INDEX idx_videos_bvid_hash ON videos(bvid_hash varchar_pattern_ops);
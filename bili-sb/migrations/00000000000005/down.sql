@@ -0,0 +1,2 @@
+DROP INDEX idx_video_parts_cid_hash;
+ALTER TABLE video_parts DROP COLUMN cid_hash;
@@ -0,0 +1,5 @@
+-- SHA-256 of cid in decimal, lowercase hex, for hash prefix lookups
+ALTER TABLE video_parts ADD COLUMN cid_hash VARCHAR(64) NOT NULL
+  GENERATED ALWAYS AS (encode(sha256(decode(cid::TEXT, 'escape')), 'hex')) STORED;
+
+CREATE INDEX idx_video_parts_cid_hash ON video_parts(cid_hash varchar_pattern_ops);
@@ -0,0 +1,3 @@
+-- public ids are one-way hashes, secrets can't be recovered from them, and running up.sql again
+-- would hash them twice and lock every user out
+DO $$ BEGIN RAISE EXCEPTION 'irreversible: user ids are one-way hashes'; END $$;
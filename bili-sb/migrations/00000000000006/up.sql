@@ -0,0 +1,16 @@
+-- `users.id` used to be the credential itself, replace it with the public id derived from it,
+-- which is the first 16 bytes of SHA-256 of the secret, see `db::User::public_id`
+ALTER TABLE segments DROP CONSTRAINT segments_submitter_fkey;
+ALTER TABLE votes DROP CONSTRAINT votes_voter_fkey;
+
+UPDATE users
+  SET id = encode(substring(sha256(uuid_send(id)) FROM 1 FOR 16), 'hex')::UUID;
+UPDATE segments
+  SET submitter = encode(substring(sha256(uuid_send(submitter)) FROM 1 FOR 16), 'hex')::UUID;
+UPDATE votes
+  SET voter = encode(substring(sha256(uuid_send(voter)) FROM 1 FOR 16), 'hex')::UUID;
+
+ALTER TABLE segments
+  ADD CONSTRAINT segments_submitter_fkey FOREIGN KEY (submitter) REFERENCES users(id);
+ALTER TABLE votes
+  ADD CONSTRAINT votes_voter_fkey FOREIGN KEY (voter) REFERENCES users(id);
@@ -0,0 +1,5 @@
+DROP TABLE locks;
+ALTER TABLE segments DROP COLUMN hidden;
+ALTER TABLE users DROP COLUMN banned;
+ALTER TABLE users DROP COLUMN role;
+DROP TYPE user_role;
@@ -0,0 +1,14 @@
+CREATE TYPE user_role AS ENUM ('normal', 'vip', 'moderator', 'admin');
+
+ALTER TABLE users ADD COLUMN role   user_role NOT NULL DEFAULT 'normal';
+ALTER TABLE users ADD COLUMN banned BOOLEAN   NOT NULL DEFAULT FALSE;
+
+-- hidden by moderators, never served
+ALTER TABLE segments ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- only VIPs and above can submit segments to locked videos
+CREATE TABLE locks (
+  aid    BIGINT    NOT NULL PRIMARY KEY REFERENCES videos(aid),
+  locker UUID      NOT NULL REFERENCES users(id),
+  "time" TIMESTAMP NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP TABLE bans;
+ALTER TABLE users DROP COLUMN shadow_banned;
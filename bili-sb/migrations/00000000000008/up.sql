@@ -0,0 +1,13 @@
+-- submissions and votes of shadow banned users are accepted but never counted or served
+ALTER TABLE users ADD COLUMN shadow_banned BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE bans (
+  ip     CIDR      NOT NULL PRIMARY KEY,
+  -- shadow banned ranges can still write, their users are shadow banned instead
+  shadow BOOLEAN   NOT NULL,
+  reason TEXT      NOT NULL,
+  actor  UUID      NOT NULL REFERENCES users(id),
+  "time" TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_bans_ip ON bans USING GIST (ip inet_ops);
@@ -0,0 +1,41 @@
+CREATE TYPE audit_action AS ENUM (
+  'segment_hide',
+  'segment_unhide',
+  'video_lock',
+  'video_unlock',
+  'user_ban',
+  'user_unban',
+  'user_shadow_ban',
+  'user_shadow_unban',
+  'user_role',
+  'ip_ban',
+  'ip_unban',
+  'vote_reset'
+);
+
+CREATE TABLE audit_log (
+  id     BIGSERIAL    NOT NULL PRIMARY KEY,
+  -- NULL for automatic state changes
+  actor  UUID         REFERENCES users(id),
+  action audit_action NOT NULL,
+  -- segment id, aid, user public id or ip range, depending on action
+  target TEXT         NOT NULL,
+  reason TEXT         NOT NULL,
+  -- previous state of target, to revert mistakes
+  detail TEXT         NOT NULL,
+  "time" TIMESTAMP    NOT NULL
+);
+
+CREATE INDEX idx_audit_log_actor ON audit_log (actor);
+CREATE INDEX idx_audit_log_target ON audit_log (target);
+CREATE INDEX idx_audit_log_time ON audit_log ("time");
+
+CREATE FUNCTION audit_log_append_only() RETURNS TRIGGER AS $$
+BEGIN
+  RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+  BEFORE UPDATE OR DELETE ON audit_log
+  FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
@@ -0,0 +1,2 @@
+-- enum values cannot be dropped, `segment_edit` is kept in audit_action
+DROP TABLE segment_revisions;
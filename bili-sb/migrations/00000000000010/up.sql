@@ -0,0 +1,17 @@
+-- previous versions of edited segments
+CREATE TABLE segment_revisions (
+  id          BIGSERIAL        NOT NULL PRIMARY KEY,
+  segment     UUID             NOT NULL REFERENCES segments(id),
+  category    segment_category NOT NULL,
+  "start"     REAL             NOT NULL,
+  "end"       REAL             NOT NULL,
+  editor      UUID             NOT NULL REFERENCES users(id),
+  editor_ip   CIDR             NOT NULL,
+  -- whether votes on this version were removed by the edit
+  votes_reset BOOLEAN          NOT NULL,
+  "time"      TIMESTAMP        NOT NULL
+);
+
+CREATE INDEX idx_segment_revisions_segment ON segment_revisions(segment);
+
+ALTER TYPE audit_action ADD VALUE 'segment_edit';
@@ -0,0 +1,2 @@
+-- enum values cannot be dropped, `segment_delete` and `segment_restore` are kept in audit_action
+ALTER TABLE segments DROP COLUMN deleted_at;
@@ -0,0 +1,5 @@
+-- soft delete, deleted segments are kept for audits and undo
+ALTER TABLE segments ADD COLUMN deleted_at TIMESTAMP;
+
+ALTER TYPE audit_action ADD VALUE 'segment_delete';
+ALTER TYPE audit_action ADD VALUE 'segment_restore';
@@ -0,0 +1,8 @@
+-- duration of the video part when the segment was submitted,
+-- segments drift when it differs from the current `video_parts.duration`
+ALTER TABLE segments ADD COLUMN video_duration REAL;
+
+UPDATE segments SET video_duration = video_parts.duration
+  FROM video_parts WHERE video_parts.cid = segments.cid;
+
+ALTER TABLE segments ALTER COLUMN video_duration SET NOT NULL;
@@ -0,0 +1,2 @@
+-- enum values cannot be dropped, `segment_auto_hide` and `segment_auto_unhide` are kept in audit_action
+ALTER TABLE segments DROP COLUMN auto_hidden;
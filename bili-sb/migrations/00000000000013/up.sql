@@ -0,0 +1,5 @@
+-- hidden by vote score, see `[voting]` config, separate from `hidden` set by moderators
+ALTER TABLE segments ADD COLUMN auto_hidden BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TYPE audit_action ADD VALUE 'segment_auto_hide';
+ALTER TYPE audit_action ADD VALUE 'segment_auto_unhide';
@@ -0,0 +1 @@
+DROP FUNCTION vote_network;
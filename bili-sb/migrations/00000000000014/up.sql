@@ -0,0 +1,4 @@
+-- network of a voter, votes from the same network count once in effective vote counts
+CREATE FUNCTION vote_network(ip CIDR, ipv4_prefix INTEGER, ipv6_prefix INTEGER) RETURNS CIDR AS $$
+  SELECT set_masklen(ip, CASE WHEN family(ip) = 4 THEN ipv4_prefix ELSE ipv6_prefix END)
+$$ LANGUAGE SQL IMMUTABLE STRICT;
@@ -0,0 +1,2 @@
+ALTER TABLE segments DROP COLUMN low_confidence;
+ALTER TABLE users DROP COLUMN trust;
@@ -0,0 +1,5 @@
+-- reputation from votes on past segments, account age and moderation, refreshed periodically
+ALTER TABLE users ADD COLUMN trust REAL NOT NULL DEFAULT 0;
+
+-- submitted by a user with low trust, not served as best segment until voted up
+ALTER TABLE segments ADD COLUMN low_confidence BOOLEAN NOT NULL DEFAULT FALSE;
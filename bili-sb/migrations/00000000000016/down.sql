@@ -0,0 +1,3 @@
+-- enum values cannot be dropped, `segment_suggestion` is kept in audit_action
+DROP TABLE suggestions;
+DROP TYPE suggestion_kind;
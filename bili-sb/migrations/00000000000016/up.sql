@@ -0,0 +1,18 @@
+CREATE TYPE suggestion_kind AS ENUM ('category', 'start', 'end');
+
+-- proposed changes to a segment, applied once enough voters agree
+CREATE TABLE suggestions (
+  segment  UUID             NOT NULL REFERENCES segments(id),
+  voter    UUID             NOT NULL REFERENCES users(id),
+  kind     suggestion_kind  NOT NULL,
+  -- set for `category` suggestions
+  category segment_category,
+  -- set for `start` and `end` suggestions
+  "value"  REAL,
+  voter_ip CIDR             NOT NULL,
+  "time"   TIMESTAMP        NOT NULL,
+  PRIMARY KEY (segment, voter, kind),
+  CHECK ((kind = 'category') = (category IS NOT NULL AND "value" IS NULL))
+);
+
+ALTER TYPE audit_action ADD VALUE 'segment_suggestion';
@@ -0,0 +1,9 @@
+DELETE FROM locks WHERE cid IS NOT NULL OR category IS NOT NULL;
+DROP INDEX idx_locks_part_category;
+DROP INDEX idx_locks_part;
+DROP INDEX idx_locks_video_category;
+DROP INDEX idx_locks_video;
+ALTER TABLE locks DROP COLUMN category;
+ALTER TABLE locks DROP COLUMN cid;
+ALTER TABLE locks DROP COLUMN id;
+ALTER TABLE locks ADD PRIMARY KEY (aid);
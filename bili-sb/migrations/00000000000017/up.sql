@@ -0,0 +1,11 @@
+-- locks are scoped to a video, or one of its parts, and optionally to a category
+ALTER TABLE locks DROP CONSTRAINT locks_pkey;
+ALTER TABLE locks ADD COLUMN id BIGSERIAL NOT NULL PRIMARY KEY;
+ALTER TABLE locks ADD COLUMN cid BIGINT REFERENCES video_parts(cid);
+ALTER TABLE locks ADD COLUMN category segment_category;
+
+-- one lock per scope, NULLs are distinct in unique constraints
+CREATE UNIQUE INDEX idx_locks_video ON locks(aid) WHERE cid IS NULL AND category IS NULL;
+CREATE UNIQUE INDEX idx_locks_video_category ON locks(aid, category) WHERE cid IS NULL AND category IS NOT NULL;
+CREATE UNIQUE INDEX idx_locks_part ON locks(cid) WHERE cid IS NOT NULL AND category IS NULL;
+CREATE UNIQUE INDEX idx_locks_part_category ON locks(cid, category) WHERE cid IS NOT NULL AND category IS NOT NULL;
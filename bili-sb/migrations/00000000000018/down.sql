@@ -0,0 +1,4 @@
+-- enum values cannot be dropped, `segment_approve` and `segment_dismiss` are kept in audit_action
+ALTER TABLE segments DROP COLUMN reviewed_at;
+DROP TABLE reports;
+DROP TYPE report_reason;
@@ -0,0 +1,22 @@
+CREATE TYPE report_reason AS ENUM ('spam', 'wrong_timing', 'offensive');
+
+-- reports of segments by users, open until resolved in the moderation queue
+CREATE TABLE reports (
+  id          BIGSERIAL     NOT NULL PRIMARY KEY,
+  segment     UUID          NOT NULL REFERENCES segments(id),
+  reporter    UUID          NOT NULL REFERENCES users(id),
+  reporter_ip CIDR          NOT NULL,
+  reason      report_reason NOT NULL,
+  comment     TEXT          NOT NULL DEFAULT '',
+  "time"      TIMESTAMP     NOT NULL,
+  resolved_at TIMESTAMP
+);
+
+-- one open report per user and segment
+CREATE UNIQUE INDEX idx_reports_open ON reports(segment, reporter) WHERE resolved_at IS NULL;
+
+-- low confidence segments leave the moderation queue once reviewed
+ALTER TABLE segments ADD COLUMN reviewed_at TIMESTAMP;
+
+ALTER TYPE audit_action ADD VALUE 'segment_approve';
+ALTER TYPE audit_action ADD VALUE 'segment_dismiss';
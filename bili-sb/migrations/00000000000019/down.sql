@@ -0,0 +1,4 @@
+DROP INDEX idx_videos_update_time;
+ALTER TABLE video_parts DROP COLUMN removed_at;
+ALTER TABLE videos DROP COLUMN unavailable_at;
+ALTER TABLE videos DROP COLUMN viewed_at;
@@ -0,0 +1,10 @@
+-- when segments of the video were last listed, videos with recent views are refreshed
+ALTER TABLE videos ADD COLUMN viewed_at TIMESTAMP;
+-- set once bilibili reports the video deleted or private, cleared when it is visible again
+ALTER TABLE videos ADD COLUMN unavailable_at TIMESTAMP;
+
+-- set once the part is no longer listed by the video, or the video is unavailable,
+-- segments of removed parts are stale
+ALTER TABLE video_parts ADD COLUMN removed_at TIMESTAMP;
+
+CREATE INDEX idx_videos_update_time ON videos(update_time);
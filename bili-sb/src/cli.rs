@@ -1,6 +1,6 @@
 use clap::{
   builder::{styling::AnsiColor, Styles},
-  Parser, ValueHint,
+  Parser, Subcommand, ValueHint,
 };
 use std::path::PathBuf;
 
@@ -32,4 +32,18 @@ pub struct Args {
   #[arg(value_hint = ValueHint::FilePath)]
   #[arg(env = "BILI_SB_CONFIG")]
   pub config: Option<PathBuf>,
+  /// Do not run background jobs in the server, e.g. when `worker` runs them
+  #[arg(long = "no-jobs")]
+  #[arg(env = "BILI_SB_NO_JOBS")]
+  pub no_jobs: bool,
+  #[command(subcommand)]
+  pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+  /// Serves the API, the default
+  Serve,
+  /// Only runs background jobs, see `--no-jobs`
+  Worker,
 }
@@ -0,0 +1,139 @@
+//! Request metadata describing the client device, see [crate::config::UpstreamMetadataConfig]
+
+use std::{sync::Arc, time::SystemTime};
+
+use anyhow::Context;
+use log::info;
+use prost::Message;
+use tonic::{
+  metadata::{Ascii, Binary, MetadataValue},
+  service::Interceptor,
+  Request, Status,
+};
+
+use super::bilibili::metadata::{
+  device::Device,
+  locale::{Locale, LocaleIds},
+  network::{Network, NetworkType},
+  Metadata,
+};
+use crate::config::{NetworkKind, UpstreamMetadataConfig};
+
+/// Attaches `x-bili-*-bin` headers, encoded once from config, to each request
+#[derive(Debug, Clone)]
+pub struct BiliInterceptor(Arc<Headers>);
+
+#[derive(Debug)]
+struct Headers {
+  metadata: MetadataValue<Binary>,
+  device: MetadataValue<Binary>,
+  locale: MetadataValue<Binary>,
+  network: MetadataValue<Binary>,
+  authorization: Option<MetadataValue<Ascii>>,
+}
+
+impl BiliInterceptor {
+  pub fn new(config: &UpstreamMetadataConfig) -> anyhow::Result<Self> {
+    let buvid = match &config.buvid {
+      Some(buvid) => buvid.clone(),
+      None => {
+        let buvid = generate_buvid();
+        info!("Generated buvid `{}` for bilibili requests", buvid);
+        buvid
+      },
+    };
+    let access_key = config.access_key.clone().unwrap_or_default();
+
+    let metadata = Metadata {
+      access_key: access_key.clone(),
+      mobi_app: config.mobi_app.clone(),
+      device: config.device.clone(),
+      build: config.build,
+      channel: config.channel.clone(),
+      buvid: buvid.clone(),
+      platform: config.platform.clone(),
+    };
+    let device = Device {
+      build: config.build,
+      buvid,
+      mobi_app: config.mobi_app.clone(),
+      platform: config.platform.clone(),
+      device: config.device.clone(),
+      channel: config.channel.clone(),
+      brand: config.brand.clone(),
+      model: config.model.clone(),
+      osver: config.osver.clone(),
+      version_name: config.version_name.clone(),
+      fts: SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|since| since.as_secs() as i64)
+        .unwrap_or_default(),
+      ..Default::default()
+    };
+    let locale_ids = LocaleIds {
+      language: config.language.clone(),
+      region: config.region.clone(),
+      ..Default::default()
+    };
+    let locale = Locale {
+      c_locale: Some(locale_ids.clone()),
+      s_locale: Some(locale_ids),
+      timezone: config.timezone.clone(),
+      ..Default::default()
+    };
+    let network_type = match config.network {
+      NetworkKind::Wifi => NetworkType::Wifi,
+      NetworkKind::Cellular => NetworkType::Cellular,
+      NetworkKind::Ethernet => NetworkType::Ethernet,
+      NetworkKind::Other => NetworkType::Othernet,
+    };
+    let network = Network {
+      r#type: network_type as i32,
+      ..Default::default()
+    };
+
+    let authorization = config
+      .access_key
+      .as_ref()
+      .map(|access_key| MetadataValue::try_from(format!("identify_v1 {}", access_key)))
+      .transpose()
+      .context("Invalid characters in `upstream.metadata.access-key`")?;
+
+    Ok(Self(Arc::new(Headers {
+      metadata: MetadataValue::from_bytes(&metadata.encode_to_vec()),
+      device: MetadataValue::from_bytes(&device.encode_to_vec()),
+      locale: MetadataValue::from_bytes(&locale.encode_to_vec()),
+      network: MetadataValue::from_bytes(&network.encode_to_vec()),
+      authorization,
+    })))
+  }
+}
+
+impl Interceptor for BiliInterceptor {
+  fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+    let headers = &self.0;
+    let meta = request.metadata_mut();
+    meta.insert_bin("x-bili-metadata-bin", headers.metadata.clone());
+    meta.insert_bin("x-bili-device-bin", headers.device.clone());
+    meta.insert_bin("x-bili-locale-bin", headers.locale.clone());
+    meta.insert_bin("x-bili-network-bin", headers.network.clone());
+    if let Some(authorization) = &headers.authorization {
+      meta.insert("authorization", authorization.clone());
+    }
+    Ok(request)
+  }
+}
+
+/// Random device id in the format of android clients, `XY`, 3 check characters, then 32 hex
+/// digits
+fn generate_buvid() -> String {
+  let id: String = rand::random::<[u8; 16]>()
+    .iter()
+    .map(|byte| format!("{:02X}", byte))
+    .collect();
+  let check: String = [2, 12, 22]
+    .iter()
+    .map(|i| id.as_bytes()[*i] as char)
+    .collect();
+  format!("XY{}{}", check, id)
+}
@@ -0,0 +1,214 @@
+//! Bilibili gRPC endpoints with failover, see [crate::config::UpstreamConfig]
+
+use std::{
+  future::Future,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use log::{info, warn};
+use tonic::{
+  service::interceptor::InterceptedService,
+  transport::{Certificate, Channel, ClientTlsConfig, Endpoint},
+  Code, Status,
+};
+
+use super::{bili_error_code, BiliInterceptor};
+use crate::config::UpstreamConfig;
+
+/// Channel to an endpoint, requests carry headers of [BiliInterceptor]
+pub type BiliChannel = InterceptedService<Channel, BiliInterceptor>;
+
+#[derive(Debug)]
+pub struct Upstream {
+  endpoints: Box<[UpstreamEndpoint]>,
+  interceptor: BiliInterceptor,
+  failure_threshold: u32,
+  cooldown: Duration,
+}
+
+#[derive(Debug)]
+struct UpstreamEndpoint {
+  endpoint: Endpoint,
+  channel: Mutex<Channel>,
+  health: Mutex<Health>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+  /// Failed requests in a row
+  failures: u32,
+  down_until: Option<Instant>,
+}
+
+impl Health {
+  fn is_up(&self, now: Instant) -> bool {
+    match self.down_until {
+      Some(until) => now >= until,
+      None => true,
+    }
+  }
+
+  /// Returns whether the endpoint just went down
+  fn fail(&mut self, now: Instant, threshold: u32, cooldown: Duration) -> bool {
+    let was_up = self.is_up(now);
+    self.failures = self.failures.saturating_add(1);
+    // once over the threshold, a single failure after the cooldown puts it down again
+    if self.failures >= threshold {
+      self.down_until = Some(now + cooldown);
+    }
+    was_up && !self.is_up(now)
+  }
+
+  fn succeed(&mut self) {
+    *self = Self::default();
+  }
+}
+
+impl Upstream {
+  pub fn new(config: &UpstreamConfig) -> anyhow::Result<Self> {
+    if config.endpoints.is_empty() {
+      anyhow::bail!("No bilibili endpoint configured in `upstream.endpoints`");
+    }
+
+    let mut tls = ClientTlsConfig::new();
+    if let Some(path) = &config.tls.ca_cert {
+      let pem = std::fs::read(path)
+        .with_context(|| format!("Failed to read CA certificate `{}`", path.to_string_lossy()))?;
+      tls = tls.ca_certificate(Certificate::from_pem(pem));
+    }
+    if let Some(domain) = &config.tls.domain {
+      tls = tls.domain_name(domain);
+    }
+
+    let endpoints = config
+      .endpoints
+      .iter()
+      .map(|uri| {
+        let mut endpoint = Endpoint::from_shared(uri.clone())
+          .with_context(|| format!("Invalid bilibili endpoint `{}`", uri))?
+          .connect_timeout(config.connect_timeout)
+          .timeout(config.timeout);
+        if endpoint.uri().scheme_str() == Some("https") {
+          endpoint = endpoint
+            .tls_config(tls.clone())
+            .with_context(|| format!("Invalid TLS config for bilibili endpoint `{}`", uri))?;
+        }
+        // connects on first request and reconnects on its own, see also [Upstream::call]
+        let channel = endpoint.connect_lazy();
+        Ok(UpstreamEndpoint {
+          endpoint,
+          channel: Mutex::new(channel),
+          health: Default::default(),
+        })
+      })
+      .collect::<anyhow::Result<_>>()?;
+
+    let interceptor = BiliInterceptor::new(&config.metadata)?;
+
+    Ok(Self {
+      endpoints,
+      interceptor,
+      failure_threshold: config.failure_threshold.get(),
+      cooldown: config.cooldown,
+    })
+  }
+
+  /// Calls endpoints in order until one answers, endpoints which are down are tried last,
+  /// earliest recovering first, see [crate::pb_client]
+  pub async fn call<T, F, Fut>(&self, mut call: F) -> Result<T, Status>
+  where
+    F: FnMut(BiliChannel) -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+  {
+    let mut last_error = None;
+    for index in self.order(Instant::now()) {
+      let endpoint = &self.endpoints[index];
+      let channel = endpoint.channel.lock().unwrap().clone();
+      match call(InterceptedService::new(channel, self.interceptor.clone())).await {
+        Err(status) if is_endpoint_failure(&status) => {
+          warn!(
+            "Bilibili endpoint `{}` failed, {}",
+            endpoint.endpoint.uri(),
+            status
+          );
+          self.fail(endpoint);
+          last_error = Some(status);
+        },
+        result => {
+          endpoint.health.lock().unwrap().succeed();
+          return result;
+        },
+      }
+    }
+    Err(last_error.unwrap_or_else(|| Status::unavailable("No bilibili endpoint available")))
+  }
+
+  fn order(&self, now: Instant) -> Vec<usize> {
+    let health: Vec<(bool, Option<Instant>)> = self
+      .endpoints
+      .iter()
+      .map(|endpoint| {
+        let health = endpoint.health.lock().unwrap();
+        (health.is_up(now), health.down_until)
+      })
+      .collect();
+    let mut order: Vec<usize> = (0..health.len()).collect();
+    // stable, so endpoints which are up keep the configured order
+    order.sort_by_key(|index| match health[*index] {
+      (true, _) => (false, None),
+      (false, until) => (true, until),
+    });
+    order
+  }
+
+  fn fail(&self, endpoint: &UpstreamEndpoint) {
+    let went_down =
+      endpoint
+        .health
+        .lock()
+        .unwrap()
+        .fail(Instant::now(), self.failure_threshold, self.cooldown);
+    if went_down {
+      info!(
+        "Bilibili endpoint `{}` is down for {:?}",
+        endpoint.endpoint.uri(),
+        self.cooldown
+      );
+      // start over with a new connection once it is tried again
+      *endpoint.channel.lock().unwrap() = endpoint.endpoint.connect_lazy();
+    }
+  }
+}
+
+/// Whether the endpoint, rather than bilibili, failed the call
+fn is_endpoint_failure(status: &Status) -> bool {
+  bili_error_code(status).is_none()
+    && matches!(
+      status.code(),
+      // `Cancelled` for timeouts, `Unknown` for transport errors
+      Code::Unavailable | Code::DeadlineExceeded | Code::Cancelled | Code::Unknown
+    )
+}
+
+#[test]
+fn health_test() {
+  const COOLDOWN: Duration = Duration::from_secs(30);
+  let now = Instant::now();
+  let mut health = Health::default();
+
+  assert!(!health.fail(now, 2, COOLDOWN));
+  assert!(health.is_up(now));
+  assert!(health.fail(now, 2, COOLDOWN));
+  assert!(!health.is_up(now + COOLDOWN / 2));
+
+  let later = now + COOLDOWN;
+  assert!(health.is_up(later));
+  assert!(health.fail(later, 2, COOLDOWN));
+  assert!(!health.is_up(later));
+
+  health.succeed();
+  assert!(health.is_up(later));
+  assert!(!health.fail(later, 2, COOLDOWN));
+}
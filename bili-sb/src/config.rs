@@ -6,10 +6,11 @@ use std::{
   fs::File,
   io::{BufReader, Read},
   num::{NonZeroU32, NonZeroU64, NonZeroUsize},
-  path::Path,
+  path::{Path, PathBuf},
   time::Duration,
 };
 use tower_governor::governor::{GovernorConfig, GovernorConfigBuilder};
+use uuid::Uuid;
 
 use serde::Deserialize;
 
@@ -28,6 +29,27 @@ pub struct Config {
   pub ratelimit: Ratelimits,
   #[serde(default)]
   pub pow: PowConfig,
+  #[serde(default)]
+  pub sponsorblock: SponsorBlockConfig,
+  #[serde(default)]
+  pub segment_edit: SegmentEditConfig,
+  #[serde(default)]
+  pub segment_duplicate: SegmentDuplicateConfig,
+  #[serde(default)]
+  pub voting: VotingConfig,
+  #[serde(default)]
+  pub trust: TrustConfig,
+  #[serde(default)]
+  pub suggestion: SuggestionConfig,
+  #[serde(default)]
+  pub video_cache: VideoCacheConfig,
+  #[serde(default)]
+  pub metadata_refresh: MetadataRefreshConfig,
+  #[serde(default)]
+  pub upstream: UpstreamConfig,
+  /// Public ids of users to be promoted to admin on startup
+  #[serde(default)]
+  pub admins: Vec<Uuid>,
 }
 
 impl Config {
@@ -128,3 +150,245 @@ pub struct PowConfig {
   #[serde(default = "pow_timestamp_delta_default")]
   pub timestamp_delta: u64,
 }
+
+/// SponsorBlock compatible API under `/api`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct SponsorBlockConfig {
+  #[serde(default = "sponsorblock_enabled_default")]
+  pub enabled: bool,
+  /// SponsorBlock clients know nothing about PoW, exempt `/api` routes from it
+  #[serde(default = "sponsorblock_exempt_pow_default")]
+  pub exempt_pow: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct SegmentEditConfig {
+  #[serde(default = "segment_edit_votes_default")]
+  pub votes: VoteCarryOver,
+}
+
+/// What happens to votes of a segment when its boundaries or category are edited
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum VoteCarryOver {
+  /// Always remove votes
+  Reset,
+  /// Always keep votes
+  Keep,
+  /// Keep votes if category is unchanged and both boundaries moved by at most this many seconds,
+  /// e.g. `votes = { tolerance = 1.0 }`
+  Tolerance(f32),
+}
+
+impl VoteCarryOver {
+  /// Whether votes on the old version still apply to the new one
+  pub fn keeps_votes(self, category_changed: bool, old: (f32, f32), new: (f32, f32)) -> bool {
+    match self {
+      Self::Reset => false,
+      Self::Keep => true,
+      Self::Tolerance(seconds) => {
+        !category_changed && (old.0 - new.0).abs() <= seconds && (old.1 - new.1).abs() <= seconds
+      },
+    }
+  }
+}
+
+/// Submissions overlapping an existing segment of the same category and action type
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct SegmentDuplicateConfig {
+  /// Minimum intersection over union to be considered duplicated, above 1.0 to disable
+  #[serde(default = "segment_duplicate_overlap_ratio_default")]
+  pub overlap_ratio: f32,
+  #[serde(default = "segment_duplicate_action_default")]
+  pub action: DuplicateAction,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateAction {
+  /// Upvote the existing segment on behalf of the submitter instead
+  Upvote,
+  /// Reject the submission
+  Reject,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct VotingConfig {
+  /// Segments with score (up - down) at or below this are hidden automatically
+  #[serde(default = "voting_hide_threshold_default")]
+  pub hide_threshold: i64,
+  /// Segments with fewer votes are never hidden automatically
+  #[serde(default = "voting_min_votes_default")]
+  pub min_votes: u32,
+  /// Votes of VIPs and above, and of users with trust at or above `trusted`, count double
+  #[serde(default = "voting_vip_double_default")]
+  pub vip_double: bool,
+  /// See [crate::trust]
+  #[serde(default = "voting_trusted_default")]
+  pub trusted: f32,
+  /// Votes from the same IPv4 network of this prefix length count once, 32 for per address
+  #[serde(default = "voting_ipv4_prefix_default")]
+  pub ipv4_prefix: u8,
+  /// Votes from the same IPv6 network of this prefix length count once, 128 for per address
+  #[serde(default = "voting_ipv6_prefix_default")]
+  pub ipv6_prefix: u8,
+}
+
+impl VotingConfig {
+  /// Weight of a vote cast by a user, votes of users with negative trust do not count
+  pub fn weight(&self, role: crate::db::UserRole, trust: f32) -> i64 {
+    if trust < 0.0 && role < crate::db::UserRole::Vip {
+      0
+    } else if self.vip_double && (role >= crate::db::UserRole::Vip || trust >= self.trusted) {
+      2
+    } else {
+      1
+    }
+  }
+
+  pub fn network(&self) -> crate::db::VoteNetwork {
+    crate::db::VoteNetwork::new(self.ipv4_prefix, self.ipv6_prefix)
+  }
+
+  pub fn should_hide(&self, score: i64, votes: usize) -> bool {
+    votes >= self.min_votes as usize && score <= self.hide_threshold
+  }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrustConfig {
+  /// How often trust of every user is recomputed
+  #[serde(with = "humantime_serde")]
+  #[serde(default = "trust_refresh_interval_default")]
+  pub refresh_interval: Duration,
+  /// New segments of users below this trust start as low confidence, VIPs are exempt
+  #[serde(default = "trust_low_confidence_below_default")]
+  pub low_confidence_below: f32,
+}
+
+/// Category changes and boundary adjustments proposed along with votes
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct SuggestionConfig {
+  /// Weighted support needed to apply a suggestion, voters of the same network count once and
+  /// are weighted by [VotingConfig::weight]
+  #[serde(default = "suggestion_threshold_default")]
+  pub threshold: i64,
+}
+
+/// Video metadata stored on submission, see [crate::metadata]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct VideoCacheConfig {
+  /// Stored metadata refreshed within this is used instead of fetching it from bilibili, `0s` to
+  /// always fetch
+  #[serde(with = "humantime_serde")]
+  #[serde(default = "video_cache_fresh_for_default")]
+  pub fresh_for: Duration,
+}
+
+/// Periodic refresh of video metadata, see [crate::metadata::refresh_videos]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct MetadataRefreshConfig {
+  #[serde(with = "humantime_serde")]
+  #[serde(default = "metadata_refresh_interval_default")]
+  pub interval: Duration,
+  /// Videos with segments submitted or listed within this are refreshed
+  #[serde(with = "humantime_serde")]
+  #[serde(default = "metadata_refresh_recent_default")]
+  pub recent: Duration,
+  /// Maximum number of videos refreshed in each run
+  #[serde(default = "metadata_refresh_batch_default")]
+  pub batch: u32,
+}
+
+/// Bilibili gRPC endpoints, see [crate::client::Upstream]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct UpstreamConfig {
+  /// Tried in order, e.g. `["http://127.0.0.1:50051"]` for a local mock server
+  #[serde(default = "upstream_endpoints_default")]
+  pub endpoints: Vec<String>,
+  #[serde(with = "humantime_serde")]
+  #[serde(default = "upstream_connect_timeout_default")]
+  pub connect_timeout: Duration,
+  /// Timeout of each request to an endpoint
+  #[serde(with = "humantime_serde")]
+  #[serde(default = "upstream_timeout_default")]
+  pub timeout: Duration,
+  /// An endpoint failing this many requests in a row is skipped for `cooldown`, and tried again
+  /// afterwards, or earlier if every endpoint is skipped
+  #[serde(default = "upstream_failure_threshold_default")]
+  pub failure_threshold: NonZeroU32,
+  #[serde(with = "humantime_serde")]
+  #[serde(default = "upstream_cooldown_default")]
+  pub cooldown: Duration,
+  /// Applies to `https` endpoints only
+  #[serde(default)]
+  pub tls: UpstreamTlsConfig,
+  #[serde(default)]
+  pub metadata: UpstreamMetadataConfig,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct UpstreamTlsConfig {
+  /// PEM file of a CA certificate trusted in addition to the bundled roots
+  pub ca_cert: Option<PathBuf>,
+  /// Name verified against certificates instead of the endpoint host, e.g. behind a proxy
+  pub domain: Option<String>,
+}
+
+/// Client device sent along with each request, see [crate::client::BiliInterceptor]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct UpstreamMetadataConfig {
+  #[serde(default = "upstream_metadata_mobi_app_default")]
+  pub mobi_app: String,
+  #[serde(default = "upstream_metadata_platform_default")]
+  pub platform: String,
+  #[serde(default = "upstream_metadata_device_default")]
+  pub device: String,
+  #[serde(default = "upstream_metadata_channel_default")]
+  pub channel: String,
+  /// Build number of the app, should match `version-name`
+  #[serde(default = "upstream_metadata_build_default")]
+  pub build: i32,
+  #[serde(default = "upstream_metadata_version_name_default")]
+  pub version_name: String,
+  #[serde(default = "upstream_metadata_brand_default")]
+  pub brand: String,
+  #[serde(default = "upstream_metadata_model_default")]
+  pub model: String,
+  #[serde(default = "upstream_metadata_osver_default")]
+  pub osver: String,
+  /// Device id, a random one is generated on startup if not set
+  #[serde(default)]
+  pub buvid: Option<String>,
+  /// Requests are sent as this logged in user if set
+  #[serde(default)]
+  pub access_key: Option<String>,
+  #[serde(default = "upstream_metadata_language_default")]
+  pub language: String,
+  #[serde(default = "upstream_metadata_region_default")]
+  pub region: String,
+  #[serde(default = "upstream_metadata_timezone_default")]
+  pub timezone: String,
+  #[serde(default = "upstream_metadata_network_default")]
+  pub network: NetworkKind,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NetworkKind {
+  Wifi,
+  Cellular,
+  Ethernet,
+  Other,
+}
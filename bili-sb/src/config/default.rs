@@ -6,6 +6,16 @@ impl Default for Config {
       ip_source: ip_source_default(),
       ratelimit: Default::default(),
       pow: Default::default(),
+      sponsorblock: Default::default(),
+      segment_edit: Default::default(),
+      segment_duplicate: Default::default(),
+      voting: Default::default(),
+      trust: Default::default(),
+      suggestion: Default::default(),
+      video_cache: Default::default(),
+      metadata_refresh: Default::default(),
+      upstream: Default::default(),
+      admins: Default::default(),
     }
   }
 }
@@ -67,3 +77,297 @@ pub fn pow_cost_default() -> u32 {
 pub fn pow_timestamp_delta_default() -> u64 {
   60
 }
+
+impl Default for SponsorBlockConfig {
+  fn default() -> Self {
+    Self {
+      enabled: sponsorblock_enabled_default(),
+      exempt_pow: sponsorblock_exempt_pow_default(),
+    }
+  }
+}
+
+#[inline]
+pub fn sponsorblock_enabled_default() -> bool {
+  false
+}
+
+#[inline]
+pub fn sponsorblock_exempt_pow_default() -> bool {
+  false
+}
+
+impl Default for SegmentEditConfig {
+  fn default() -> Self {
+    Self {
+      votes: segment_edit_votes_default(),
+    }
+  }
+}
+
+#[inline]
+pub fn segment_edit_votes_default() -> VoteCarryOver {
+  VoteCarryOver::Tolerance(1.0)
+}
+
+impl Default for SegmentDuplicateConfig {
+  fn default() -> Self {
+    Self {
+      overlap_ratio: segment_duplicate_overlap_ratio_default(),
+      action: segment_duplicate_action_default(),
+    }
+  }
+}
+
+#[inline]
+pub fn segment_duplicate_overlap_ratio_default() -> f32 {
+  0.8
+}
+
+#[inline]
+pub fn segment_duplicate_action_default() -> DuplicateAction {
+  DuplicateAction::Upvote
+}
+
+impl Default for VotingConfig {
+  fn default() -> Self {
+    Self {
+      hide_threshold: voting_hide_threshold_default(),
+      min_votes: voting_min_votes_default(),
+      vip_double: voting_vip_double_default(),
+      trusted: voting_trusted_default(),
+      ipv4_prefix: voting_ipv4_prefix_default(),
+      ipv6_prefix: voting_ipv6_prefix_default(),
+    }
+  }
+}
+
+#[inline]
+pub fn voting_hide_threshold_default() -> i64 {
+  -2
+}
+
+#[inline]
+pub fn voting_min_votes_default() -> u32 {
+  3
+}
+
+#[inline]
+pub fn voting_vip_double_default() -> bool {
+  true
+}
+
+#[inline]
+pub fn voting_trusted_default() -> f32 {
+  5.0
+}
+
+#[inline]
+pub fn voting_ipv4_prefix_default() -> u8 {
+  32
+}
+
+#[inline]
+pub fn voting_ipv6_prefix_default() -> u8 {
+  64
+}
+
+impl Default for TrustConfig {
+  fn default() -> Self {
+    Self {
+      refresh_interval: trust_refresh_interval_default(),
+      low_confidence_below: trust_low_confidence_below_default(),
+    }
+  }
+}
+
+#[inline]
+pub fn trust_refresh_interval_default() -> Duration {
+  Duration::from_secs(60 * 60)
+}
+
+#[inline]
+pub fn trust_low_confidence_below_default() -> f32 {
+  0.0
+}
+
+impl Default for SuggestionConfig {
+  fn default() -> Self {
+    Self {
+      threshold: suggestion_threshold_default(),
+    }
+  }
+}
+
+#[inline]
+pub fn suggestion_threshold_default() -> i64 {
+  3
+}
+
+impl Default for VideoCacheConfig {
+  fn default() -> Self {
+    Self {
+      fresh_for: video_cache_fresh_for_default(),
+    }
+  }
+}
+
+#[inline]
+pub fn video_cache_fresh_for_default() -> Duration {
+  Duration::from_secs(10 * 60)
+}
+
+impl Default for MetadataRefreshConfig {
+  fn default() -> Self {
+    Self {
+      interval: metadata_refresh_interval_default(),
+      recent: metadata_refresh_recent_default(),
+      batch: metadata_refresh_batch_default(),
+    }
+  }
+}
+
+#[inline]
+pub fn metadata_refresh_interval_default() -> Duration {
+  Duration::from_secs(60 * 60)
+}
+
+#[inline]
+pub fn metadata_refresh_recent_default() -> Duration {
+  Duration::from_secs(7 * 24 * 60 * 60)
+}
+
+#[inline]
+pub fn metadata_refresh_batch_default() -> u32 {
+  100
+}
+
+impl Default for UpstreamConfig {
+  fn default() -> Self {
+    Self {
+      endpoints: upstream_endpoints_default(),
+      connect_timeout: upstream_connect_timeout_default(),
+      timeout: upstream_timeout_default(),
+      failure_threshold: upstream_failure_threshold_default(),
+      cooldown: upstream_cooldown_default(),
+      tls: Default::default(),
+      metadata: Default::default(),
+    }
+  }
+}
+
+#[inline]
+pub fn upstream_endpoints_default() -> Vec<String> {
+  // the failover endpoint has been the only one in use, keep it first
+  vec![
+    crate::client::BILI_GRPC_FAILOVER_URL.to_string(),
+    crate::client::BILI_GRPC_URL.to_string(),
+  ]
+}
+
+#[inline]
+pub fn upstream_connect_timeout_default() -> Duration {
+  Duration::from_secs(5)
+}
+
+#[inline]
+pub fn upstream_timeout_default() -> Duration {
+  Duration::from_secs(10)
+}
+
+#[inline]
+pub fn upstream_failure_threshold_default() -> NonZeroU32 {
+  unsafe { NonZeroU32::new_unchecked(3) }
+}
+
+#[inline]
+pub fn upstream_cooldown_default() -> Duration {
+  Duration::from_secs(30)
+}
+
+impl Default for UpstreamMetadataConfig {
+  fn default() -> Self {
+    Self {
+      mobi_app: upstream_metadata_mobi_app_default(),
+      platform: upstream_metadata_platform_default(),
+      device: upstream_metadata_device_default(),
+      channel: upstream_metadata_channel_default(),
+      build: upstream_metadata_build_default(),
+      version_name: upstream_metadata_version_name_default(),
+      brand: upstream_metadata_brand_default(),
+      model: upstream_metadata_model_default(),
+      osver: upstream_metadata_osver_default(),
+      buvid: None,
+      access_key: None,
+      language: upstream_metadata_language_default(),
+      region: upstream_metadata_region_default(),
+      timezone: upstream_metadata_timezone_default(),
+      network: upstream_metadata_network_default(),
+    }
+  }
+}
+
+#[inline]
+pub fn upstream_metadata_mobi_app_default() -> String {
+  "android".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_platform_default() -> String {
+  "android".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_device_default() -> String {
+  "phone".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_channel_default() -> String {
+  "master".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_build_default() -> i32 {
+  7380300
+}
+
+#[inline]
+pub fn upstream_metadata_version_name_default() -> String {
+  "7.38.0".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_brand_default() -> String {
+  "Xiaomi".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_model_default() -> String {
+  "2211133C".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_osver_default() -> String {
+  "13".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_language_default() -> String {
+  "zh".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_region_default() -> String {
+  "CN".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_timezone_default() -> String {
+  "Asia/Shanghai".to_string()
+}
+
+#[inline]
+pub fn upstream_metadata_network_default() -> NetworkKind {
+  NetworkKind::Wifi
+}
@@ -1,6 +1,8 @@
-use std::{borrow::Cow, num::NonZeroU64};
+use std::{borrow::Cow, num::NonZeroU64, str::FromStr};
 
+use anyhow::Context;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 
 #[derive(Debug, Clone, Copy)]
 #[repr(transparent)]
@@ -49,6 +51,28 @@ impl Abv {
     #[cfg(debug_assertions)]
     abv::av2bv(self.0.get()).unwrap()
   }
+
+  /// SHA-256 of [Abv::bv] in lowercase hex, used by hash prefix lookups
+  pub fn bv_hash(self) -> String {
+    format!("{:x}", Sha256::digest(self.bv().as_bytes()))
+  }
+}
+
+/// Parses `BV1...`, `av170001` or plain `170001`
+impl FromStr for Abv {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let aid = s
+      .strip_prefix("av")
+      .or_else(|| s.strip_prefix("AV"))
+      .unwrap_or(s);
+    if let Ok(aid) = aid.parse::<u64>() {
+      return Abv::new(aid).context("invalid aid, number out of range");
+    }
+    let aid = abv::bv2av(s).with_context(|| format!("invalid bvid `{s}`"))?;
+    Ok(unsafe { Abv::new_unchecked(aid) })
+  }
 }
 
 impl<'de> Deserialize<'de> for Abv {
@@ -105,4 +129,10 @@ fn abv_test() {
   let json = json!({"bvid": "BV0asdfas"});
   let data = serde_json::from_value::<Test>(json);
   assert!(data.is_err());
+
+  assert_eq!("BV1Gb4y1C78H".parse::<Abv>().unwrap().av(), 631295196);
+  assert_eq!("av170001".parse::<Abv>().unwrap().av(), 170001);
+  assert_eq!("170001".parse::<Abv>().unwrap().av(), 170001);
+  assert!("av0".parse::<Abv>().is_err());
+  assert!("BV0asdfas".parse::<Abv>().is_err());
 }
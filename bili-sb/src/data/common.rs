@@ -79,8 +79,16 @@ macro_rules! resp_codes {
 resp_codes! {
   (0, SUCCESS),
   (1, INVALID_PARAMS),
+  (2, PERMISSION_DENIED),
+  (3, USER_BANNED),
+  (4, IP_BANNED),
   (100, DATABASE_ERROR),
   (101, BILI_CLIENT_ERROR),
+  (200, VIDEO_LOCKED),
+  (201, SEGMENT_DUPLICATED),
+  (202, SCOPE_LOCKED),
+  (203, SEGMENT_LOCKED),
+  (204, VIDEO_UNAVAILABLE),
   (10000, UNKNOWN),
 }
 
@@ -0,0 +1,61 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::bail;
+
+/// Leading lowercase hex characters of a SHA-256 digest
+///
+/// Clients send it instead of the exact video, so that the server can't tell which one they watch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct HashPrefix(String);
+
+impl HashPrefix {
+  /// Shorter prefixes match too many videos to be useful
+  pub const MIN_LEN: usize = 4;
+  pub const MAX_LEN: usize = 64;
+
+  /// `LIKE` pattern matching hashes starting with this prefix
+  pub fn like_pattern(&self) -> String {
+    format!("{}%", self.0)
+  }
+}
+
+impl fmt::Display for HashPrefix {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+impl FromStr for HashPrefix {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if !(Self::MIN_LEN..=Self::MAX_LEN).contains(&s.len()) {
+      bail!(
+        "hash prefix should be {} to {} characters",
+        Self::MIN_LEN,
+        Self::MAX_LEN
+      );
+    }
+    if !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+      bail!("hash prefix should be hex");
+    }
+    Ok(HashPrefix(s.to_ascii_lowercase()))
+  }
+}
+
+#[test]
+fn hash_prefix_test() {
+  assert_eq!(
+    "ABcd12".parse::<HashPrefix>().unwrap().to_string(),
+    "abcd12"
+  );
+  assert_eq!(
+    "abcd".parse::<HashPrefix>().unwrap().like_pattern(),
+    "abcd%"
+  );
+  assert!("abc".parse::<HashPrefix>().is_err());
+  assert!("abcg".parse::<HashPrefix>().is_err());
+  assert!("a%bc".parse::<HashPrefix>().is_err());
+  assert!("a".repeat(65).parse::<HashPrefix>().is_err());
+}
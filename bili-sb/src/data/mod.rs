@@ -1,5 +1,9 @@
 mod abv;
 mod common;
+mod hash_prefix;
+mod overlap;
 
 pub use self::abv::*;
 pub use common::*;
+pub use hash_prefix::*;
+pub use overlap::*;
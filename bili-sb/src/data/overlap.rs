@@ -0,0 +1,23 @@
+/// Intersection over union of two `(start, end)` ranges, in `0.0..=1.0`
+///
+/// Zero-length ranges (points of interest and full video labels) only overlap when they are equal
+pub fn overlap_ratio(a: (f32, f32), b: (f32, f32)) -> f32 {
+  let union = a.1.max(b.1) - a.0.min(b.0);
+  if union <= 0.0 {
+    return if a == b { 1.0 } else { 0.0 };
+  }
+  let intersection = (a.1.min(b.1) - a.0.max(b.0)).max(0.0);
+  intersection / union
+}
+
+#[test]
+fn overlap_test() {
+  assert_eq!(overlap_ratio((0.0, 10.0), (0.0, 10.0)), 1.0);
+  assert_eq!(overlap_ratio((0.0, 10.0), (5.0, 15.0)), 5.0 / 15.0);
+  assert_eq!(overlap_ratio((0.0, 10.0), (1.0, 9.0)), 0.8);
+  assert_eq!(overlap_ratio((0.0, 10.0), (10.0, 20.0)), 0.0);
+  assert_eq!(overlap_ratio((0.0, 10.0), (20.0, 30.0)), 0.0);
+  assert_eq!(overlap_ratio((0.0, 0.0), (0.0, 0.0)), 1.0);
+  assert_eq!(overlap_ratio((5.0, 5.0), (5.0, 5.0)), 1.0);
+  assert_eq!(overlap_ratio((5.0, 5.0), (6.0, 6.0)), 0.0);
+}
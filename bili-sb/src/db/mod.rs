@@ -1,14 +1,20 @@
 use std::time::SystemTime;
 
 use diesel::{
-  pg::Pg, AsChangeset, BoolExpressionMethods, ExpressionMethods, Insertable, QueryDsl, Queryable,
-  Selectable,
+  dsl::{count_distinct, count_star, exists},
+  pg::Pg,
+  sql_function,
+  sql_types::{Array, BigInt, Cidr, Integer, Text},
+  AsChangeset, BoolExpressionMethods, ExpressionMethods, Insertable, JoinOnDsl,
+  NullableExpressionMethods, OptionalExtension, PgNetExpressionMethods, QueryDsl, Queryable,
+  Selectable, SelectableHelper, TextExpressionMethods,
 };
-use diesel_async::RunQueryDsl;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
 
 use diesel_derive_enum::DbEnum;
 use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 #[rustfmt::skip]
@@ -16,7 +22,10 @@ mod schema;
 
 pub use schema::*;
 
-use crate::state::PooledPgCon;
+use crate::{
+  data::{Abv, HashPrefix},
+  state::PooledPgCon,
+};
 
 #[derive(Debug, Insertable, Queryable, Selectable, AsChangeset)]
 #[diesel(table_name = videos)]
@@ -25,6 +34,8 @@ pub struct Video {
   pub aid: i64,
   pub title: String,
   pub update_time: SystemTime,
+  /// See [Abv::bv_hash]
+  pub bvid_hash: Option<String>,
 }
 
 #[derive(Debug, Insertable, Queryable, Selectable, AsChangeset)]
@@ -41,23 +52,63 @@ pub struct VideoPart {
 #[diesel(table_name = users)]
 #[diesel(check_for_backend(Pg))]
 pub struct User {
+  /// Public id, see [User::public_id]
   pub id: Uuid,
   pub register_time: SystemTime,
   pub register_ip: IpNet,
   pub last_operation_ip: Option<IpNet>,
   pub last_operation_time: Option<SystemTime>,
+  pub role: UserRole,
+  pub banned: bool,
+  pub shadow_banned: bool,
+  /// See [crate::trust]
+  pub trust: f32,
 }
 
 impl User {
-  pub fn new(ip: IpNet) -> User {
+  /// `secret` is the credential held by the client, it is never stored
+  pub fn new(secret: &Uuid, ip: IpNet) -> User {
     Self {
-      id: Uuid::new_v4(),
+      id: Self::public_id(secret),
       register_time: SystemTime::now(),
       register_ip: ip,
       last_operation_ip: None,
       last_operation_time: None,
+      role: UserRole::Normal,
+      banned: false,
+      shadow_banned: false,
+      trust: 0.0,
     }
   }
+
+  /// The first 16 bytes of SHA-256 of the secret
+  ///
+  /// Secrets are random v4 uuids, so no salt is needed to keep them irreversible.
+  pub fn public_id(secret: &Uuid) -> Uuid {
+    let digest = Sha256::digest(secret.as_bytes());
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    Uuid::from_bytes(bytes)
+  }
+}
+
+/// Ordered by privilege, every role has all permissions of the roles before it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, DbEnum)]
+#[ExistingTypePath = "schema::sql_types::UserRole"]
+#[serde(rename_all = "snake_case")]
+pub enum UserRole {
+  Normal,
+  /// Trusted submitter, can submit to locked videos
+  Vip,
+  /// Can access `/admin`, and manage users with lower roles
+  Moderator,
+  Admin,
+}
+
+impl Default for UserRole {
+  fn default() -> Self {
+    Self::Normal
+  }
 }
 
 #[derive(Serialize, Clone, Debug, Insertable, Queryable, Selectable)]
@@ -66,29 +117,275 @@ impl User {
 pub struct Segment {
   pub id: Uuid,
   pub cid: i64,
+  pub category: SegmentCategory,
+  pub action_type: ActionType,
   pub start: f32,
   pub end: f32,
   #[serde(with = "humantime_serde")]
   pub time: SystemTime,
-  #[serde(skip)]
+  /// Public id of the submitter
   pub submitter: Uuid,
   #[serde(skip)]
   pub submitter_ip: IpNet,
+  pub hidden: bool,
+  /// Soft deleted by the submitter or a moderator
+  #[serde(with = "humantime_serde")]
+  pub deleted_at: Option<SystemTime>,
+  /// Duration of the video part at submission time
+  pub video_duration: f32,
+  /// Hidden by vote score, see [crate::config::VotingConfig]
+  pub auto_hidden: bool,
+  /// Submitted by a user with low trust, see [crate::config::TrustConfig]
+  pub low_confidence: bool,
+  /// Last resolved in the moderation queue
+  #[serde(with = "humantime_serde")]
+  pub reviewed_at: Option<SystemTime>,
+}
+
+/// A replaced version of an edited [Segment]
+#[derive(Serialize, Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = segment_revisions)]
+#[diesel(check_for_backend(Pg))]
+pub struct SegmentRevision {
+  pub id: i64,
+  pub segment: Uuid,
+  pub category: SegmentCategory,
+  pub start: f32,
+  pub end: f32,
+  /// Public id of the editor
+  pub editor: Uuid,
+  pub votes_reset: bool,
+  /// When this version was replaced
+  #[serde(with = "humantime_serde")]
+  pub time: SystemTime,
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = segment_revisions)]
+#[diesel(check_for_backend(Pg))]
+pub struct NewSegmentRevision {
+  pub segment: Uuid,
+  pub category: SegmentCategory,
+  pub start: f32,
+  pub end: f32,
+  pub editor: Uuid,
+  pub editor_ip: IpNet,
+  pub votes_reset: bool,
+  pub time: SystemTime,
 }
 
 #[derive(Serialize, Clone, Debug, Queryable)]
 pub struct SegmentWithVote {
   pub id: Uuid,
   pub cid: i64,
+  pub category: SegmentCategory,
+  pub action_type: ActionType,
   pub start: f32,
   pub end: f32,
   #[serde(with = "humantime_serde")]
   pub time: SystemTime,
   pub up_vote: Option<i64>,
   pub down_vote: Option<i64>,
+  /// Votes from the same network counted once, see [VoteNetwork]
+  pub effective_up_vote: Option<i64>,
+  /// Votes from the same network counted once, see [VoteNetwork]
+  pub effective_down_vote: Option<i64>,
+  /// Duration of the video part changed since submission, e.g. re-uploaded, the range may drift,
+  /// or the part has been removed, see [store_video] and [mark_video_unavailable]
+  pub stale: bool,
+  /// See [Segment::low_confidence]
+  pub low_confidence: bool,
+  /// Used to rank segments, see [crate::selection]
+  #[serde(skip)]
+  pub submitter_role: UserRole,
+  /// Used to rank segments, see [crate::selection]
+  #[serde(skip)]
+  pub submitter_trust: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, DbEnum)]
+#[ExistingTypePath = "schema::sql_types::SegmentCategory"]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentCategory {
+  /// Paid promotion or referral
+  Sponsor,
+  /// Opening animation or intro without actual content
+  Intro,
+  /// Credits or endcards
+  Outro,
+  /// Unpaid promotion of the uploader's own products, merch or channels
+  #[serde(rename = "selfpromo")]
+  #[db_rename = "selfpromo"]
+  SelfPromo,
+  /// Reminders to like, coin, favorite or follow
+  Interaction,
+  /// Recap of previous episodes or preview of upcoming content
+  Preview,
+  /// Tangents or jokes not required to understand the main content
+  Filler,
+  /// Non-music parts of a music video
+  MusicOfftopic,
+}
+
+impl Default for SegmentCategory {
+  fn default() -> Self {
+    Self::Sponsor
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "schema::sql_types::ActionType"]
+#[serde(rename_all = "snake_case")]
+pub enum ActionType {
+  /// Skip the range `start..end`
+  Skip,
+  /// Mute the range `start..end`
+  Mute,
+  /// The whole video is labeled with the category, `start` and `end` are both 0
+  Full,
+  /// Point of interest, jump to `start` (which equals to `end`)
+  Poi,
+}
+
+impl Default for ActionType {
+  fn default() -> Self {
+    Self::Skip
+  }
+}
+
+#[derive(Clone, Debug, Insertable, Queryable, Selectable)]
+#[diesel(table_name = bans)]
+#[diesel(check_for_backend(Pg))]
+pub struct Ban {
+  pub ip: IpNet,
+  pub shadow: bool,
+  pub reason: String,
+  pub actor: Uuid,
+  pub time: SystemTime,
+}
+
+/// Only VIPs and above can submit to, edit or downvote in the scope of a lock
+#[derive(Serialize, Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = locks)]
+#[diesel(check_for_backend(Pg))]
+pub struct Lock {
+  pub id: i64,
+  pub aid: i64,
+  /// `None` for every part of the video
+  pub cid: Option<i64>,
+  /// `None` for every category
+  pub category: Option<SegmentCategory>,
+  pub locker: Uuid,
+  #[serde(with = "humantime_serde")]
+  pub time: SystemTime,
+}
+
+impl Lock {
+  /// Whether segments of `category` in part `cid` of the locked video are in scope
+  pub fn covers(&self, cid: i64, category: SegmentCategory) -> bool {
+    (self.cid.is_none() || self.cid == Some(cid))
+      && (self.category.is_none() || self.category == Some(category))
+  }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = locks)]
+#[diesel(check_for_backend(Pg))]
+pub struct NewLock {
+  pub aid: i64,
+  pub cid: Option<i64>,
+  pub category: Option<SegmentCategory>,
+  pub locker: Uuid,
+  pub time: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "schema::sql_types::AuditAction"]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+  SegmentHide,
+  SegmentUnhide,
+  /// `detail` is the part and category of the lock
+  VideoLock,
+  /// `detail` is the part and category of the lock
+  VideoUnlock,
+  UserBan,
+  UserUnban,
+  UserShadowBan,
+  UserShadowUnban,
+  /// `detail` is the previous role
+  UserRole,
+  IpBan,
+  IpUnban,
+  /// `detail` is the number of removed votes
+  VoteReset,
+  /// Edit of another user's segment, previous version is in [SegmentRevision]
+  SegmentEdit,
+  SegmentDelete,
+  SegmentRestore,
+  /// `detail` is the vote score and count
+  SegmentAutoHide,
+  /// `detail` is the vote score and count
+  SegmentAutoUnhide,
+  /// Accepted [Suggestion], `detail` is the applied change and number of supporting networks
+  SegmentSuggestion,
+  /// Confirmed in the moderation queue, `detail` is the number of resolved reports
+  SegmentApprove,
+  /// Removed from the moderation queue unchanged, `detail` is the number of resolved reports
+  SegmentDismiss,
+}
+
+/// Append-only, updates and deletes are rejected by a trigger
+#[derive(Serialize, Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = audit_log)]
+#[diesel(check_for_backend(Pg))]
+pub struct AuditLog {
+  pub id: i64,
+  /// `None` for automatic state changes
+  pub actor: Option<Uuid>,
+  pub action: AuditAction,
+  pub target: String,
+  pub reason: String,
+  pub detail: String,
+  pub time: SystemTime,
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = audit_log)]
+#[diesel(check_for_backend(Pg))]
+pub struct NewAuditLog {
+  pub actor: Option<Uuid>,
+  pub action: AuditAction,
+  pub target: String,
+  pub reason: String,
+  pub detail: String,
+  pub time: SystemTime,
+}
+
+impl NewAuditLog {
+  pub fn new(actor: Option<Uuid>, action: AuditAction, target: impl ToString) -> Self {
+    Self {
+      actor,
+      action,
+      target: target.to_string(),
+      reason: String::new(),
+      detail: String::new(),
+      time: SystemTime::now(),
+    }
+  }
+
+  pub fn reason(mut self, reason: impl Into<String>) -> Self {
+    self.reason = reason.into();
+    self
+  }
+
+  pub fn detail(mut self, detail: impl ToString) -> Self {
+    self.detail = detail.to_string();
+    self
+  }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, DbEnum)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, DbEnum)]
 #[ExistingTypePath = "schema::sql_types::VoteType"]
 #[serde(rename_all = "snake_case")]
 pub enum VoteType {
@@ -113,6 +410,86 @@ pub struct Vote {
   pub time: SystemTime,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, DbEnum)]
+#[ExistingTypePath = "schema::sql_types::SuggestionKind"]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+  Category,
+  Start,
+  End,
+}
+
+/// A change proposed by a voter, at most one of each kind per voter and segment
+#[derive(Clone, Debug, Insertable, Queryable, Selectable, AsChangeset)]
+#[diesel(table_name = suggestions)]
+#[diesel(check_for_backend(Pg))]
+#[diesel(treat_none_as_null = true)]
+pub struct Suggestion {
+  pub segment: Uuid,
+  pub voter: Uuid,
+  pub kind: SuggestionKind,
+  /// Set for [SuggestionKind::Category]
+  pub category: Option<SegmentCategory>,
+  /// Set for [SuggestionKind::Start] and [SuggestionKind::End]
+  pub value: Option<f32>,
+  pub voter_ip: IpNet,
+  pub time: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, DbEnum)]
+#[ExistingTypePath = "schema::sql_types::ReportReason"]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+  Spam,
+  WrongTiming,
+  Offensive,
+}
+
+#[derive(Serialize, Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = reports)]
+#[diesel(check_for_backend(Pg))]
+pub struct Report {
+  pub id: i64,
+  pub segment: Uuid,
+  /// Public id of the reporter
+  pub reporter: Uuid,
+  pub reason: ReportReason,
+  pub comment: String,
+  #[serde(with = "humantime_serde")]
+  pub time: SystemTime,
+  /// Set once handled in the moderation queue
+  #[serde(with = "humantime_serde")]
+  pub resolved_at: Option<SystemTime>,
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = reports)]
+#[diesel(check_for_backend(Pg))]
+pub struct NewReport {
+  pub segment: Uuid,
+  pub reporter: Uuid,
+  pub reporter_ip: IpNet,
+  pub reason: ReportReason,
+  pub comment: String,
+  pub time: SystemTime,
+}
+
+/// Ids of shadow banned users, whose segments and votes are never counted or served
+macro_rules! shadow_banned_users {
+  () => {
+    users::table
+      .filter(users::shadow_banned.eq(true))
+      .select(users::id)
+  };
+}
+
+/// Submitter of the segment, whose own votes are never counted
+macro_rules! submitter_of {
+  ($segment:expr) => {
+    segments::table.find($segment).select(segments::submitter)
+  };
+}
+
 pub async fn count_votes(
   con: &mut PooledPgCon<'_>,
   segment: Uuid,
@@ -120,80 +497,756 @@ pub async fn count_votes(
 ) -> diesel::QueryResult<i64> {
   votes::table
     .filter(votes::segment.eq(segment).and(votes::type_.eq(vote_type)))
+    .filter(votes::voter.ne_all(shadow_banned_users!()))
+    .filter(votes::voter.ne_all(submitter_of!(segment)))
     .count()
     .get_result(con)
     .await
 }
 
+/// Votes of a segment with roles, trust and ips of the voters, votes of shadow banned users and
+/// the submitter are excluded
+pub async fn votes_with_roles(
+  con: &mut AsyncPgConnection,
+  segment: Uuid,
+) -> diesel::QueryResult<Vec<(VoteType, UserRole, f32, IpNet)>> {
+  votes::table
+    .inner_join(users::table)
+    .filter(votes::segment.eq(segment))
+    .filter(users::shadow_banned.eq(false))
+    .filter(votes::voter.ne_all(submitter_of!(segment)))
+    .select((votes::type_, users::role, users::trust, votes::voter_ip))
+    .load(con)
+    .await
+}
+
+/// Suggestions on a segment with role and trust of their voters, excluding shadow banned users
+/// and the submitter
+pub async fn suggestions_with_roles(
+  con: &mut AsyncPgConnection,
+  segment: Uuid,
+) -> diesel::QueryResult<Vec<(Suggestion, UserRole, f32)>> {
+  suggestions::table
+    .inner_join(users::table)
+    .filter(suggestions::segment.eq(segment))
+    .filter(users::shadow_banned.eq(false))
+    .filter(suggestions::voter.ne_all(submitter_of!(segment)))
+    .select((Suggestion::as_select(), users::role, users::trust))
+    .load(con)
+    .await
+}
+
+diesel::alias!(users as voters: VotersAlias);
+
+/// [shadow_banned_users] selected from an alias, for queries which already join `users`
+macro_rules! shadow_banned_voters {
+  () => {
+    voters
+      .filter(voters.field(users::shadow_banned).eq(true))
+      .select(voters.field(users::id))
+  };
+}
+
+/// Votes of type `$vote_type` on the segment of the outer query, which are counted
+macro_rules! counted_votes {
+  ($vote_type:expr) => {
+    votes::table.filter(
+      votes::segment
+        .eq(segments::id)
+        .and(votes::type_.eq($vote_type))
+        .and(votes::voter.ne_all(shadow_banned_voters!()))
+        .and(votes::voter.ne(segments::submitter)),
+    )
+  };
+}
+
 macro_rules! vote_query {
   ($vote_type:expr) => {
-    votes::table
-      .filter(
-        votes::segment
-          .eq(segments::id)
-          .and(votes::type_.eq($vote_type)),
-      )
-      .count()
+    counted_votes!($vote_type).count().single_value()
+  };
+}
+
+macro_rules! effective_vote_query {
+  ($vote_type:expr, $network:expr) => {
+    counted_votes!($vote_type)
+      .select(count_distinct(vote_network(
+        votes::voter_ip,
+        $network.ipv4_prefix as i32,
+        $network.ipv6_prefix as i32,
+      )))
       .single_value()
   };
 }
 
-pub async fn segments_related_to_aid(
+sql_function! {
+  /// See migration `00000000000014`
+  fn vote_network(ip: Cidr, ipv4_prefix: Integer, ipv6_prefix: Integer) -> Cidr;
+}
+
+/// Prefix lengths of networks, votes from the same network count once in effective vote counts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoteNetwork {
+  pub ipv4_prefix: u8,
+  pub ipv6_prefix: u8,
+}
+
+impl VoteNetwork {
+  pub fn new(ipv4_prefix: u8, ipv6_prefix: u8) -> Self {
+    Self {
+      ipv4_prefix: ipv4_prefix.min(32),
+      ipv6_prefix: ipv6_prefix.min(128),
+    }
+  }
+
+  /// Same as `vote_network` in SQL
+  pub fn network_of(self, ip: IpNet) -> IpNet {
+    let prefix = match ip {
+      IpNet::V4(_) => self.ipv4_prefix,
+      IpNet::V6(_) => self.ipv6_prefix,
+    };
+    IpNet::new(ip.addr(), prefix)
+      .map(|network| network.trunc())
+      .unwrap_or(ip)
+  }
+}
+
+/// Optional filters applied to `segments_related_to_*` queries
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SegmentFilter {
+  /// Only return segments in these categories, empty for all
+  #[serde(default)]
+  pub categories: Vec<SegmentCategory>,
+  /// Only return segments with these action types, empty for all
+  #[serde(default)]
+  pub action_types: Vec<ActionType>,
+}
+
+enum SegmentTarget<'a> {
+  Aid(i64),
+  Aids(&'a [i64]),
+  Cid(i64),
+  Cids(&'a [i64]),
+  BvidHash(&'a HashPrefix),
+  CidHash(&'a HashPrefix),
+}
+
+async fn segments_related_to(
   con: &mut PooledPgCon<'_>,
-  aid: i64,
+  target: SegmentTarget<'_>,
+  filter: &SegmentFilter,
+  network: VoteNetwork,
 ) -> diesel::QueryResult<Vec<SegmentWithVote>> {
-  videos::table
-    .inner_join(video_parts::table.inner_join(segments::table))
+  let mut query = video_parts::table
+    .inner_join(segments::table)
+    .inner_join(users::table.on(users::id.eq(segments::submitter)))
     .select((
       segments::id,
       segments::cid,
+      segments::category,
+      segments::action_type,
       segments::start,
       segments::end,
       segments::time,
       vote_query!(VoteType::Up),
       vote_query!(VoteType::Down),
+      effective_vote_query!(VoteType::Up, network),
+      effective_vote_query!(VoteType::Down, network),
+      segments::video_duration
+        .ne(video_parts::duration)
+        .or(video_parts::removed_at.is_not_null()),
+      segments::low_confidence,
+      users::role,
+      users::trust,
     ))
-    .filter(videos::aid.eq(aid))
-    .get_results::<SegmentWithVote>(con)
-    .await
+    .filter(segments::hidden.eq(false))
+    .filter(segments::auto_hidden.eq(false))
+    .filter(segments::deleted_at.is_null())
+    .filter(users::shadow_banned.eq(false))
+    .into_boxed();
+
+  query = match target {
+    SegmentTarget::Aid(aid) => query.filter(video_parts::aid.eq(aid)),
+    SegmentTarget::Aids(aids) => query.filter(video_parts::aid.eq_any(aids)),
+    SegmentTarget::Cid(cid) => query.filter(segments::cid.eq(cid)),
+    SegmentTarget::Cids(cids) => query.filter(segments::cid.eq_any(cids)),
+    SegmentTarget::BvidHash(prefix) => query.filter(
+      video_parts::aid.eq_any(
+        videos::table
+          .filter(videos::bvid_hash.like(prefix.like_pattern()))
+          .select(videos::aid),
+      ),
+    ),
+    SegmentTarget::CidHash(prefix) => {
+      query.filter(video_parts::cid_hash.like(prefix.like_pattern()))
+    },
+  };
+
+  if !filter.categories.is_empty() {
+    query = query.filter(segments::category.eq_any(&filter.categories));
+  }
+
+  if !filter.action_types.is_empty() {
+    query = query.filter(segments::action_type.eq_any(&filter.action_types));
+  }
+
+  query.get_results::<SegmentWithVote>(con).await
+}
+
+pub async fn segments_related_to_aid(
+  con: &mut PooledPgCon<'_>,
+  aid: i64,
+  filter: &SegmentFilter,
+  network: VoteNetwork,
+) -> diesel::QueryResult<Vec<SegmentWithVote>> {
+  segments_related_to(con, SegmentTarget::Aid(aid), filter, network).await
+}
+
+pub async fn segments_related_to_aids(
+  con: &mut PooledPgCon<'_>,
+  aids: &[i64],
+  filter: &SegmentFilter,
+  network: VoteNetwork,
+) -> diesel::QueryResult<Vec<SegmentWithVote>> {
+  segments_related_to(con, SegmentTarget::Aids(aids), filter, network).await
 }
 
 pub async fn segments_related_to_cid(
   con: &mut PooledPgCon<'_>,
   cid: i64,
+  filter: &SegmentFilter,
+  network: VoteNetwork,
 ) -> diesel::QueryResult<Vec<SegmentWithVote>> {
-  video_parts::table
-    .inner_join(segments::table)
-    .select((
-      segments::id,
-      segments::cid,
-      segments::start,
-      segments::end,
-      segments::time,
-      vote_query!(VoteType::Up),
-      vote_query!(VoteType::Down),
-    ))
-    .filter(video_parts::aid.eq(cid))
-    .get_results::<SegmentWithVote>(con)
-    .await
+  segments_related_to(con, SegmentTarget::Cid(cid), filter, network).await
 }
 
 pub async fn segments_related_to_cids(
   con: &mut PooledPgCon<'_>,
   cids: &[i64],
+  filter: &SegmentFilter,
+  network: VoteNetwork,
 ) -> diesel::QueryResult<Vec<SegmentWithVote>> {
+  segments_related_to(con, SegmentTarget::Cids(cids), filter, network).await
+}
+
+/// Segments of videos whose [Abv::bv_hash] starts with `prefix`
+pub async fn segments_related_to_bvid_hash(
+  con: &mut PooledPgCon<'_>,
+  prefix: &HashPrefix,
+  filter: &SegmentFilter,
+  network: VoteNetwork,
+) -> diesel::QueryResult<Vec<SegmentWithVote>> {
+  segments_related_to(con, SegmentTarget::BvidHash(prefix), filter, network).await
+}
+
+/// Segments of parts whose `video_parts.cid_hash` starts with `prefix`
+pub async fn segments_related_to_cid_hash(
+  con: &mut PooledPgCon<'_>,
+  prefix: &HashPrefix,
+  filter: &SegmentFilter,
+  network: VoteNetwork,
+) -> diesel::QueryResult<Vec<SegmentWithVote>> {
+  segments_related_to(con, SegmentTarget::CidHash(prefix), filter, network).await
+}
+
+pub async fn parts_of_cids(
+  con: &mut PooledPgCon<'_>,
+  cids: &[i64],
+) -> diesel::QueryResult<Vec<VideoPart>> {
   video_parts::table
-    .inner_join(segments::table)
+    .filter(video_parts::cid.eq_any(cids))
+    .select(VideoPart::as_select())
+    .get_results(con)
+    .await
+}
+
+/// See [Abv::bv_hash]
+pub async fn videos_with_hash_prefix(
+  con: &mut PooledPgCon<'_>,
+  prefix: &HashPrefix,
+) -> diesel::QueryResult<Vec<Video>> {
+  videos::table
+    .filter(videos::bvid_hash.like(prefix.like_pattern()))
+    .select(Video::as_select())
+    .get_results(con)
+    .await
+}
+
+/// Fills `videos.bvid_hash` for rows inserted before the column exists in a single statement,
+/// returns the number of updated rows
+pub async fn backfill_video_hashes(con: &mut PooledPgCon<'_>) -> diesel::QueryResult<usize> {
+  let aids: Vec<i64> = videos::table
+    .filter(videos::bvid_hash.is_null())
+    .select(videos::aid)
+    .get_results(con)
+    .await?;
+  if aids.is_empty() {
+    return Ok(0);
+  }
+
+  let (aids, hashes): (Vec<i64>, Vec<String>) = aids
+    .into_iter()
+    .filter_map(|aid| Some((aid, Abv::new(aid as u64)?.bv_hash())))
+    .unzip();
+  diesel::sql_query(
+    "UPDATE videos SET bvid_hash = hashes.hash \
+     FROM unnest($1, $2) AS hashes(aid, hash) \
+     WHERE videos.aid = hashes.aid AND videos.bvid_hash IS NULL",
+  )
+  .bind::<Array<BigInt>, _>(aids)
+  .bind::<Array<Text>, _>(hashes)
+  .execute(con)
+  .await
+}
+
+/// Previous versions of an edited segment, newest first
+pub async fn revisions_of_segment(
+  con: &mut PooledPgCon<'_>,
+  segment: Uuid,
+) -> diesel::QueryResult<Vec<SegmentRevision>> {
+  segment_revisions::table
+    .filter(segment_revisions::segment.eq(segment))
+    .order(segment_revisions::id.desc())
+    .select(SegmentRevision::as_select())
+    .load(con)
+    .await
+}
+
+/// Video `aid` and its listed parts, if available and refreshed after `since`
+pub async fn cached_video(
+  con: &mut AsyncPgConnection,
+  aid: i64,
+  since: SystemTime,
+) -> diesel::QueryResult<Option<(Video, Vec<VideoPart>)>> {
+  let video: Option<Video> = videos::table
+    .find(aid)
+    .filter(videos::update_time.gt(since))
+    .filter(videos::unavailable_at.is_null())
+    .select(Video::as_select())
+    .first(con)
+    .await
+    .optional()?;
+  let Some(video) = video else {
+    return Ok(None);
+  };
+
+  let parts = video_parts::table
+    .filter(video_parts::aid.eq(aid))
+    .filter(video_parts::removed_at.is_null())
+    .select(VideoPart::as_select())
+    .load(con)
+    .await?;
+  Ok(Some((video, parts)))
+}
+
+/// Changes found by [store_video]
+#[derive(Debug, Default)]
+pub struct VideoChanges {
+  /// Parts whose duration changed, their segments are stale
+  pub resized: Vec<i64>,
+  /// Parts no longer listed by the video
+  pub removed: Vec<i64>,
+}
+
+/// Upserts a video and its parts, parts no longer listed are marked removed but kept for their
+/// segments
+pub async fn store_video(
+  con: &mut AsyncPgConnection,
+  video: &Video,
+  parts: &[VideoPart],
+) -> diesel::QueryResult<VideoChanges> {
+  let previous: Vec<(i64, f32)> = video_parts::table
+    .filter(video_parts::aid.eq(video.aid))
+    .filter(video_parts::removed_at.is_null())
+    .select((video_parts::cid, video_parts::duration))
+    .load(con)
+    .await?;
+
+  diesel::insert_into(videos::table)
+    .values(video)
+    .on_conflict(videos::aid)
+    .do_update()
+    .set((video, videos::unavailable_at.eq(None::<SystemTime>)))
+    .execute(con)
+    .await?;
+
+  for part in parts {
+    diesel::insert_into(video_parts::table)
+      .values(part)
+      .on_conflict(video_parts::cid)
+      .do_update()
+      .set((part, video_parts::removed_at.eq(None::<SystemTime>)))
+      .execute(con)
+      .await?;
+  }
+
+  let cids: Vec<i64> = parts.iter().map(|part| part.cid).collect();
+  let removed: Vec<i64> = diesel::update(
+    video_parts::table
+      .filter(video_parts::aid.eq(video.aid))
+      .filter(video_parts::cid.ne_all(&cids))
+      .filter(video_parts::removed_at.is_null()),
+  )
+  .set(video_parts::removed_at.eq(video.update_time))
+  .returning(video_parts::cid)
+  .get_results(con)
+  .await?;
+
+  let resized = previous
+    .into_iter()
+    .filter(|(cid, duration)| {
+      parts
+        .iter()
+        .any(|part| part.cid == *cid && part.duration != *duration)
+    })
+    .map(|(cid, _)| cid)
+    .collect();
+
+  Ok(VideoChanges { resized, removed })
+}
+
+/// Marks a deleted or private video and its parts, returns whether it was available before
+pub async fn mark_video_unavailable(
+  con: &mut AsyncPgConnection,
+  aid: i64,
+  now: SystemTime,
+) -> diesel::QueryResult<bool> {
+  // the check time is kept in `update_time`, so that the video is not checked again right away
+  let updated = diesel::update(videos::table.find(aid))
+    .set(videos::update_time.eq(now))
+    .execute(con)
+    .await?;
+  if updated == 0 {
+    return Ok(false);
+  }
+
+  let marked = diesel::update(
+    videos::table
+      .find(aid)
+      .filter(videos::unavailable_at.is_null()),
+  )
+  .set(videos::unavailable_at.eq(now))
+  .execute(con)
+  .await?;
+
+  diesel::update(
+    video_parts::table
+      .filter(video_parts::aid.eq(aid))
+      .filter(video_parts::removed_at.is_null()),
+  )
+  .set(video_parts::removed_at.eq(now))
+  .execute(con)
+  .await?;
+
+  Ok(marked != 0)
+}
+
+/// Records that segments of parts `cids` were listed
+pub async fn mark_viewed(
+  con: &mut PooledPgCon<'_>,
+  cids: &[i64],
+  now: SystemTime,
+) -> diesel::QueryResult<usize> {
+  diesel::update(
+    videos::table.filter(
+      videos::aid.eq_any(
+        video_parts::table
+          .filter(video_parts::cid.eq_any(cids))
+          .select(video_parts::aid),
+      ),
+    ),
+  )
+  .set(videos::viewed_at.eq(now))
+  .execute(con)
+  .await
+}
+
+/// Videos with segments submitted or listed after `since`, not refreshed after
+/// `refreshed_before`, least recently refreshed first
+pub async fn videos_to_refresh(
+  con: &mut PooledPgCon<'_>,
+  since: SystemTime,
+  refreshed_before: SystemTime,
+  limit: i64,
+) -> diesel::QueryResult<Vec<i64>> {
+  videos::table
+    .filter(videos::update_time.lt(refreshed_before))
+    .filter(
+      videos::viewed_at.gt(since).or(
+        videos::aid.eq_any(
+          video_parts::table
+            .inner_join(segments::table)
+            .filter(segments::time.gt(since))
+            .select(video_parts::aid),
+        ),
+      ),
+    )
+    .order(videos::update_time.asc())
+    .limit(limit)
+    .select(videos::aid)
+    .load(con)
+    .await
+}
+
+/// The broadest lock covering segments of `category` in part `cid` of video `aid`, if any
+pub async fn lock_of(
+  con: &mut AsyncPgConnection,
+  aid: i64,
+  cid: i64,
+  category: SegmentCategory,
+) -> diesel::QueryResult<Option<Lock>> {
+  locks::table
+    .filter(locks::aid.eq(aid))
+    .filter(locks::cid.is_null().or(locks::cid.eq(cid)))
+    .filter(locks::category.is_null().or(locks::category.eq(category)))
+    .order((locks::cid.is_not_null(), locks::category.is_not_null()))
+    .select(Lock::as_select())
+    .first(con)
+    .await
+    .optional()
+}
+
+/// Every lock of the videos, see [Lock::covers]
+pub async fn locks_of_aids(
+  con: &mut PooledPgCon<'_>,
+  aids: &[i64],
+) -> diesel::QueryResult<Vec<Lock>> {
+  locks::table
+    .filter(locks::aid.eq_any(aids))
+    .select(Lock::as_select())
+    .get_results(con)
+    .await
+}
+
+/// Promotes users listed in [crate::config::Config::admins], returns the number of updated rows
+pub async fn promote_admins(
+  con: &mut AsyncPgConnection,
+  ids: &[Uuid],
+) -> diesel::QueryResult<usize> {
+  let promoted: Vec<(Uuid, UserRole)> = users::table
+    .filter(users::id.eq_any(ids).and(users::role.ne(UserRole::Admin)))
+    .select((users::id, users::role))
+    .load(con)
+    .await?;
+  if promoted.is_empty() {
+    return Ok(0);
+  }
+
+  let promoted_ids: Vec<Uuid> = promoted.iter().map(|(id, _)| *id).collect();
+  diesel::update(users::table.filter(users::id.eq_any(&promoted_ids)))
+    .set(users::role.eq(UserRole::Admin))
+    .execute(con)
+    .await?;
+
+  let entries: Vec<NewAuditLog> = promoted
+    .into_iter()
+    .map(|(id, role)| {
+      NewAuditLog::new(None, AuditAction::UserRole, id)
+        .reason("listed in config admins")
+        .detail(format!("{role:?}"))
+    })
+    .collect();
+  audit(con, &entries).await?;
+  Ok(entries.len())
+}
+
+/// The ban covering `ip`, hard bans take precedence over shadow bans
+pub async fn ip_ban(con: &mut PooledPgCon<'_>, ip: IpNet) -> diesel::QueryResult<Option<Ban>> {
+  bans::table
+    .filter(bans::ip.contains_or_eq(ip))
+    .order(bans::shadow.asc())
+    .select(Ban::as_select())
+    .first(con)
+    .await
+    .optional()
+}
+
+/// Shadow bans users registered or last operated in `ip`, returns ids of newly banned users
+pub async fn shadow_ban_users_in(
+  con: &mut AsyncPgConnection,
+  ip: IpNet,
+) -> diesel::QueryResult<Vec<Uuid>> {
+  diesel::update(
+    users::table
+      .filter(
+        users::register_ip.is_contained_by_or_eq(ip).or(
+          users::last_operation_ip
+            .assume_not_null()
+            .is_contained_by_or_eq(ip),
+        ),
+      )
+      .filter(users::shadow_banned.eq(false)),
+  )
+  .set(users::shadow_banned.eq(true))
+  .returning(users::id)
+  .get_results(con)
+  .await
+}
+
+/// Shadow bans a user operating from a shadow banned ip range, see [ip_ban]
+pub async fn shadow_ban_user(con: &mut AsyncPgConnection, id: Uuid) -> diesel::QueryResult<usize> {
+  let updated = diesel::update(users::table.find(id))
+    .set(users::shadow_banned.eq(true))
+    .execute(con)
+    .await?;
+  audit(
+    con,
+    &[NewAuditLog::new(None, AuditAction::UserShadowBan, id)
+      .reason("operated in shadow banned ip range")],
+  )
+  .await?;
+  Ok(updated)
+}
+
+// kept out of the generated `schema.rs`, which `diesel print-schema` overwrites
+diesel::allow_columns_to_appear_in_same_group_by_clause!(segments::submitter, votes::type_);
+
+/// Counted up and down votes on segments of every submitter, deleted segments are excluded
+pub async fn submitter_votes(
+  con: &mut PooledPgCon<'_>,
+) -> diesel::QueryResult<Vec<(Uuid, VoteType, i64)>> {
+  segments::table
+    .inner_join(votes::table)
+    .filter(segments::deleted_at.is_null())
+    .filter(votes::voter.ne(segments::submitter))
+    .filter(votes::voter.ne_all(shadow_banned_users!()))
+    .group_by((segments::submitter, votes::type_))
+    .select((segments::submitter, votes::type_, count_star()))
+    .load(con)
+    .await
+}
+
+/// Number of segments hidden by moderators and by votes of every submitter
+pub async fn submitter_hidden(
+  con: &mut PooledPgCon<'_>,
+) -> diesel::QueryResult<Vec<(Uuid, bool, bool, i64)>> {
+  segments::table
+    .filter(segments::hidden.eq(true).or(segments::auto_hidden.eq(true)))
+    .group_by((segments::submitter, segments::hidden, segments::auto_hidden))
     .select((
-      segments::id,
-      segments::cid,
-      segments::start,
-      segments::end,
-      segments::time,
-      vote_query!(VoteType::Up),
-      vote_query!(VoteType::Down),
+      segments::submitter,
+      segments::hidden,
+      segments::auto_hidden,
+      count_star(),
+    ))
+    .load(con)
+    .await
+}
+
+/// Appends entries to [audit_log], takes a plain connection so that it can run in transactions
+pub async fn audit(
+  con: &mut AsyncPgConnection,
+  entries: &[NewAuditLog],
+) -> diesel::QueryResult<usize> {
+  diesel::insert_into(audit_log::table)
+    .values(entries)
+    .execute(con)
+    .await
+}
+
+diesel::alias!(users as reporters: ReportersAlias);
+
+/// Open reports on the segment of the outer query, reports of shadow banned users are ignored
+///
+/// Shadow banned reporters are selected from an alias, as the outer query joins `users`
+macro_rules! open_reports {
+  () => {
+    reports::table.filter(
+      reports::segment
+        .eq(segments::id)
+        .and(reports::resolved_at.is_null())
+        .and(
+          reports::reporter.ne_all(
+            reporters
+              .filter(reporters.field(users::shadow_banned).eq(true))
+              .select(reporters.field(users::id)),
+          ),
+        ),
+    )
+  };
+}
+
+/// Visible segments with open reports, or with low confidence and never reviewed, oldest first
+///
+/// Returned with aid, video title, part title and trust of the submitter
+pub async fn moderation_queue(
+  con: &mut PooledPgCon<'_>,
+  limit: i64,
+  offset: i64,
+) -> diesel::QueryResult<Vec<(Segment, i64, String, String, f32)>> {
+  segments::table
+    .inner_join(video_parts::table.inner_join(videos::table))
+    .inner_join(users::table)
+    .filter(segments::deleted_at.is_null())
+    .filter(segments::hidden.eq(false))
+    .filter(
+      exists(open_reports!()).or(
+        segments::low_confidence
+          .eq(true)
+          .and(segments::reviewed_at.is_null()),
+      ),
+    )
+    .order((segments::time.asc(), segments::id.asc()))
+    .limit(limit)
+    .offset(offset)
+    .select((
+      Segment::as_select(),
+      videos::aid,
+      videos::title,
+      video_parts::title,
+      users::trust,
     ))
-    .filter(video_parts::aid.eq_any(cids))
-    .get_results::<SegmentWithVote>(con)
+    .load(con)
+    .await
+}
+
+/// Open reports on `segments`, reports of shadow banned users are ignored
+pub async fn open_reports_of(
+  con: &mut PooledPgCon<'_>,
+  segments: &[Uuid],
+) -> diesel::QueryResult<Vec<Report>> {
+  reports::table
+    .filter(reports::segment.eq_any(segments))
+    .filter(reports::resolved_at.is_null())
+    .filter(reports::reporter.ne_all(shadow_banned_users!()))
+    .order(reports::id.asc())
+    .select(Report::as_select())
+    .load(con)
     .await
 }
+
+/// Optional filters of [audit_logs], newest first
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AuditLogFilter {
+  pub actor: Option<Uuid>,
+  pub action: Option<AuditAction>,
+  pub target: Option<String>,
+  /// Only return entries with id smaller than this, for pagination
+  pub before: Option<i64>,
+}
+
+pub async fn audit_logs(
+  con: &mut PooledPgCon<'_>,
+  filter: &AuditLogFilter,
+  limit: i64,
+) -> diesel::QueryResult<Vec<AuditLog>> {
+  let mut query = audit_log::table
+    .select(AuditLog::as_select())
+    .order(audit_log::id.desc())
+    .limit(limit)
+    .into_boxed();
+
+  if let Some(actor) = filter.actor {
+    query = query.filter(audit_log::actor.eq(actor));
+  }
+  if let Some(action) = filter.action {
+    query = query.filter(audit_log::action.eq(action));
+  }
+  if let Some(target) = &filter.target {
+    query = query.filter(audit_log::target.eq(target));
+  }
+  if let Some(before) = filter.before {
+    query = query.filter(audit_log::id.lt(before));
+  }
+
+  query.load(con).await
+}
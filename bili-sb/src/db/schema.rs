@@ -1,12 +1,112 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "action_type"))]
+    pub struct ActionType;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "audit_action"))]
+    pub struct AuditAction;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "report_reason"))]
+    pub struct ReportReason;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "segment_category"))]
+    pub struct SegmentCategory;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "suggestion_kind"))]
+    pub struct SuggestionKind;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "user_role"))]
+    pub struct UserRole;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "vote_type"))]
     pub struct VoteType;
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AuditAction;
+
+    audit_log (id) {
+        id -> Int8,
+        actor -> Nullable<Uuid>,
+        action -> AuditAction,
+        target -> Text,
+        reason -> Text,
+        detail -> Text,
+        time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    bans (ip) {
+        ip -> Cidr,
+        shadow -> Bool,
+        reason -> Text,
+        actor -> Uuid,
+        time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::SegmentCategory;
+
+    locks (id) {
+        aid -> Int8,
+        locker -> Uuid,
+        time -> Timestamp,
+        id -> Int8,
+        cid -> Nullable<Int8>,
+        category -> Nullable<SegmentCategory>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ReportReason;
+
+    reports (id) {
+        id -> Int8,
+        segment -> Uuid,
+        reporter -> Uuid,
+        reporter_ip -> Cidr,
+        reason -> ReportReason,
+        comment -> Text,
+        time -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::SegmentCategory;
+
+    segment_revisions (id) {
+        id -> Int8,
+        segment -> Uuid,
+        category -> SegmentCategory,
+        start -> Float4,
+        end -> Float4,
+        editor -> Uuid,
+        editor_ip -> Cidr,
+        votes_reset -> Bool,
+        time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::SegmentCategory;
+    use super::sql_types::ActionType;
+
     segments (id) {
         id -> Uuid,
         cid -> Int8,
@@ -15,16 +115,47 @@ diesel::table! {
         submitter -> Uuid,
         submitter_ip -> Cidr,
         time -> Timestamp,
+        category -> SegmentCategory,
+        action_type -> ActionType,
+        hidden -> Bool,
+        deleted_at -> Nullable<Timestamp>,
+        video_duration -> Float4,
+        auto_hidden -> Bool,
+        low_confidence -> Bool,
+        reviewed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::SuggestionKind;
+    use super::sql_types::SegmentCategory;
+
+    suggestions (segment, voter, kind) {
+        segment -> Uuid,
+        voter -> Uuid,
+        kind -> SuggestionKind,
+        category -> Nullable<SegmentCategory>,
+        value -> Nullable<Float4>,
+        voter_ip -> Cidr,
+        time -> Timestamp,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::UserRole;
+
     users (id) {
         id -> Uuid,
         register_time -> Timestamp,
         register_ip -> Cidr,
         last_operation_ip -> Nullable<Cidr>,
         last_operation_time -> Nullable<Timestamp>,
+        role -> UserRole,
+        banned -> Bool,
+        shadow_banned -> Bool,
+        trust -> Float4,
     }
 }
 
@@ -35,6 +166,9 @@ diesel::table! {
         #[max_length = 160]
         title -> Varchar,
         duration -> Float4,
+        #[max_length = 64]
+        cid_hash -> Varchar,
+        removed_at -> Nullable<Timestamp>,
     }
 }
 
@@ -44,6 +178,10 @@ diesel::table! {
         #[max_length = 160]
         title -> Varchar,
         update_time -> Timestamp,
+        #[max_length = 64]
+        bvid_hash -> Nullable<Varchar>,
+        viewed_at -> Nullable<Timestamp>,
+        unavailable_at -> Nullable<Timestamp>,
     }
 }
 
@@ -61,14 +199,31 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(audit_log -> users (actor));
+diesel::joinable!(bans -> users (actor));
+diesel::joinable!(locks -> users (locker));
+diesel::joinable!(locks -> video_parts (cid));
+diesel::joinable!(locks -> videos (aid));
+diesel::joinable!(reports -> segments (segment));
+diesel::joinable!(reports -> users (reporter));
+diesel::joinable!(segment_revisions -> segments (segment));
+diesel::joinable!(segment_revisions -> users (editor));
 diesel::joinable!(segments -> users (submitter));
 diesel::joinable!(segments -> video_parts (cid));
+diesel::joinable!(suggestions -> segments (segment));
+diesel::joinable!(suggestions -> users (voter));
 diesel::joinable!(video_parts -> videos (aid));
 diesel::joinable!(votes -> segments (segment));
 diesel::joinable!(votes -> users (voter));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_log,
+    bans,
+    locks,
+    reports,
+    segment_revisions,
     segments,
+    suggestions,
     users,
     video_parts,
     videos,
@@ -0,0 +1,53 @@
+//! Periodic background jobs, run by the server unless `--no-jobs` is given, or alone by
+//! `bili-sb worker`
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use log::{debug, error, info};
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+
+use crate::{metadata, state::App, trust};
+
+pub fn spawn_jobs(state: Arc<App>) -> Vec<JoinHandle<()>> {
+  let config = Arc::clone(&state.config);
+  vec![
+    spawn_periodic(
+      Arc::clone(&state),
+      "trust refresh",
+      config.trust.refresh_interval,
+      |state| async move { trust::refresh_trust(&state).await },
+    ),
+    spawn_periodic(
+      state,
+      "metadata refresh",
+      config.metadata_refresh.interval,
+      |state| async move { metadata::refresh_videos(&state).await },
+    ),
+  ]
+}
+
+/// Runs `job` every `period`, a run is skipped if the previous one is still running
+fn spawn_periodic<F, Fut>(
+  state: Arc<App>,
+  name: &'static str,
+  period: Duration,
+  job: F,
+) -> JoinHandle<()>
+where
+  F: Fn(Arc<App>) -> Fut + Send + 'static,
+  Fut: Future<Output = anyhow::Result<usize>> + Send,
+{
+  let period = period.max(Duration::from_secs(1));
+  info!("Job `{}` scheduled every {:?}", name, period);
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+      interval.tick().await;
+      match job(Arc::clone(&state)).await {
+        Ok(processed) => debug!("Job `{}` processed {} items", name, processed),
+        Err(error) => error!("Job `{}` failed: {:?}", name, error),
+      }
+    }
+  })
+}
@@ -8,10 +8,11 @@ use axum_client_ip::*;
 use http::{Method, Request, StatusCode};
 use tower_governor::{key_extractor::KeyExtractor, GovernorError};
 
-use crate::state::*;
+use crate::{app_err_custom, data::RespCode, db, error::*, routes::authenticate, state::*};
 
 pub const POW_HEADER_UUID: &str = "bilisb-pow-uuid";
 pub const POW_HEADER_SOLUTION: &str = "bilisb-pow-solution";
+pub const USER_HEADER_SECRET: &str = "bilisb-user-secret";
 
 pub async fn pow_layer<B>(state: AppState, mut request: Request<B>, next: Next<B>) -> Response {
   let config = &state.config.pow;
@@ -25,6 +26,9 @@ pub async fn pow_layer<B>(state: AppState, mut request: Request<B>, next: Next<B
   if request.uri().path().starts_with("/pow/choose") {
     return next.run(request).await;
   }
+  if state.config.sponsorblock.exempt_pow && request.uri().path().starts_with("/api/") {
+    return next.run(request).await;
+  }
 
   let Some(uuid) = request
     .headers_mut()
@@ -71,6 +75,91 @@ pub async fn pow_layer<B>(state: AppState, mut request: Request<B>, next: Next<B
   next.run(request).await
 }
 
+/// Marks requests from shadow banned IP ranges, see [ban_layer]
+#[derive(Clone, Copy, Debug)]
+pub struct IpShadowBanned;
+
+/// Rejects POST requests from banned IP ranges
+///
+/// Requests from shadow banned ranges pass with [IpShadowBanned] extension,
+/// write routes shadow ban their users instead of telling them.
+pub async fn ban_layer<B>(
+  state: AppState,
+  ip: SecureClientIp,
+  mut request: Request<B>,
+  next: Next<B>,
+) -> Response {
+  if request.method() != Method::POST {
+    return next.run(request).await;
+  }
+
+  let ban = match ip_ban(&state, ip.0.into()).await {
+    Ok(ban) => ban,
+    Err(error) => return error.into_response(),
+  };
+
+  match ban {
+    Some(ban) if !ban.shadow => app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::IP_BANNED,
+      "IP range {} is banned",
+      ban.ip
+    )
+    .into_response(),
+    Some(_) => {
+      request.extensions_mut().insert(IpShadowBanned);
+      next.run(request).await
+    },
+    None => next.run(request).await,
+  }
+}
+
+async fn ip_ban(state: &App, ip: ipnet::IpNet) -> AppResult<Option<db::Ban>> {
+  let mut db_con = state.db_con().await?;
+  db::ip_ban(&mut db_con, ip)
+    .await
+    .with_context_into_app(|| format!("Failed to fetch ban for ip {ip}"))
+}
+
+/// Authenticates the user by header `bilisb-user-secret`, and requires [db::UserRole::Moderator]
+///
+/// The user is inserted into request extensions, extract it by `Extension<db::User>`.
+pub async fn admin_layer<B>(state: AppState, mut request: Request<B>, next: Next<B>) -> Response {
+  let Some(secret) = request
+    .headers_mut()
+    .remove(USER_HEADER_SECRET)
+    .and_then(|value| uuid::Uuid::try_parse_ascii(value.as_bytes()).ok())
+  else {
+    return (
+      StatusCode::UNAUTHORIZED,
+      "header `bilisb-user-secret` does not exist or malformed",
+    )
+      .into_response();
+  };
+
+  let user = match moderator(&state, &secret).await {
+    Ok(user) => user,
+    Err(error) => return error.into_response(),
+  };
+  request.extensions_mut().insert(user);
+
+  next.run(request).await
+}
+
+async fn moderator(state: &App, secret: &uuid::Uuid) -> AppResult<db::User> {
+  let mut db_con = state.db_con().await?;
+  let user = authenticate(&mut db_con, secret).await?;
+  if user.role < db::UserRole::Moderator {
+    return Err(app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::PERMISSION_DENIED,
+      "Moderator role is required, id = {}",
+      user.id
+    ));
+  }
+  Ok(user)
+}
+
 #[derive(Clone, Debug)]
 pub struct SecureIpExtractor;
 
@@ -28,10 +28,15 @@ mod config;
 mod data;
 mod db;
 mod error;
+mod jobs;
 mod layer;
 mod macros;
+mod metadata;
 mod routes;
+mod selection;
+mod sponsorblock;
 mod state;
+mod trust;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -78,6 +83,17 @@ async fn main() -> anyhow::Result<()> {
   }
 
   let state = Arc::new(App::new(&args.database_url, config).await?);
+  if args.command == Some(cli::Command::Worker) {
+    jobs::spawn_jobs(state);
+    info!("Worker is running background jobs");
+    tokio::signal::ctrl_c()
+      .await
+      .context("Failed to listen for shutdown signal")?;
+    return Ok(());
+  }
+  if !args.no_jobs {
+    jobs::spawn_jobs(Arc::clone(&state));
+  }
   let post_ratelimit_conf = Box::new(state.config.ratelimit_post_conf());
   info!(
     "[POST] ratelimit enabled: {:?}",
@@ -89,13 +105,30 @@ async fn main() -> anyhow::Result<()> {
     &state.config.ratelimit.post
   );
 
-  let router = Router::new()
+  let mut router = Router::new()
     .route("/", get(root))
     .route("/pow/choose", post(pow_choose))
     .route("/user/create", post(user_create))
+    .route("/user/info", get(user_info))
     .route("/segment/create", post(segment_create))
+    .route("/segment/delete", post(segment_delete))
+    .route("/segment/edit", post(segment_edit))
     .route("/segment/list", get(segment_list))
+    .route("/segment/list/hash", get(segment_list_by_hash))
+    .route("/segment/report", post(segment_report))
+    .route("/segment/revisions", get(segment_revisions))
     .route("/segment/vote", post(segment_vote))
+    .nest("/admin", admin::router(Arc::clone(&state)));
+
+  if state.config.sponsorblock.enabled {
+    info!(
+      "SponsorBlock compatible API enabled: {:?}",
+      &state.config.sponsorblock
+    );
+    router = router.nest("/api", sponsorblock::router());
+  }
+
+  let router = router
     .fallback(fallback)
     .with_state(Arc::clone(&state))
     .layer(CompressionLayer::new())
@@ -103,6 +136,10 @@ async fn main() -> anyhow::Result<()> {
       Arc::clone(&state),
       pow_layer,
     ))
+    .layer(axum::middleware::from_fn_with_state(
+      Arc::clone(&state),
+      ban_layer,
+    ))
     .layer(ratelimit!(Box::leak(get_ratelimit_conf)))
     .layer(ratelimit!(Box::leak(post_ratelimit_conf)))
     .layer(state.config.ip_source.clone().into_extension());
@@ -0,0 +1,291 @@
+//! Video metadata from bilibili, cached in `videos` and `video_parts`
+//!
+//! Metadata refreshed within [crate::config::VideoCacheConfig::fresh_for] is served from the
+//! database. Otherwise it is fetched again, concurrent fetches of one video share a single call.
+//! Videos with recent segments or views are also refreshed periodically, see [refresh_videos].
+
+use std::{
+  sync::Arc,
+  time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Context;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use http::StatusCode;
+use log::{debug, error, info, warn};
+use tokio::sync::OnceCell;
+
+use crate::{
+  app_err, app_err_custom,
+  client::*,
+  data::{Abv, RespCode},
+  db,
+  error::*,
+  pb_client,
+  state::{ADashMap, App, PooledPgCon},
+};
+
+use bilibili::app::archive::v1::Arc as Archive;
+
+#[derive(Debug)]
+pub struct VideoMetadata {
+  pub video: db::Video,
+  pub parts: Vec<db::VideoPart>,
+}
+
+impl VideoMetadata {
+  pub fn part(&self, cid: i64) -> Option<&db::VideoPart> {
+    self.parts.iter().find(|part| part.cid == cid)
+  }
+}
+
+/// Fetches in flight, keyed by aid
+pub type VideoFetches = ADashMap<i64, Arc<OnceCell<Arc<VideoMetadata>>>>;
+
+/// Last time views of each part were recorded, keyed by cid
+pub type RecordedViews = ADashMap<i64, Instant>;
+
+/// Business error codes of deleted, private or otherwise invisible videos
+const UNAVAILABLE_CODES: [i32; 4] = [-404, 62002, 62004, 62012];
+
+/// Views of a part are written at most once in this interval
+const VIEW_RECORD_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Metadata of `abv`, fetched again if stale or if part `cid` is unknown
+pub async fn video_metadata(
+  state: &App,
+  con: &mut PooledPgCon<'_>,
+  abv: Abv,
+  cid: i64,
+) -> AppResult<Arc<VideoMetadata>> {
+  let aid = abv.as_i64();
+  let fresh_for = state.config.video_cache.fresh_for;
+  if !fresh_for.is_zero() {
+    let since = SystemTime::now()
+      .checked_sub(fresh_for)
+      .unwrap_or(SystemTime::UNIX_EPOCH);
+    let cached = db::cached_video(con, aid, since)
+      .await
+      .with_context_into_app(|| format!("Failed to fetch cached video, aid = {aid}"))?;
+    if let Some((video, parts)) = cached {
+      let metadata = VideoMetadata { video, parts };
+      if metadata.part(cid).is_some() {
+        return Ok(Arc::new(metadata));
+      }
+    }
+  }
+
+  refetch(state, con, abv).await
+}
+
+/// Fetches metadata of `abv` from bilibili and stores it, sharing fetches in flight
+pub async fn refetch(
+  state: &App,
+  con: &mut PooledPgCon<'_>,
+  abv: Abv,
+) -> AppResult<Arc<VideoMetadata>> {
+  let aid = abv.as_i64();
+  // the entry guard is dropped right away, it must not be held across awaits
+  let flight = Arc::clone(state.video_fetches.entry(aid).or_default().value());
+  let result = flight
+    .get_or_try_init(|| fetch(state, con, abv))
+    .await
+    .cloned();
+  // only requests waiting on this fetch share it, later ones check the database again
+  state
+    .video_fetches
+    .remove_if(&aid, |_, current| Arc::ptr_eq(current, &flight));
+  result
+}
+
+/// Fetches metadata from bilibili and stores it
+async fn fetch(state: &App, con: &mut PooledPgCon<'_>, abv: Abv) -> AppResult<Arc<VideoMetadata>> {
+  debug!("Fetching metadata of video {}", abv.av());
+  let req = view::ViewReq {
+    aid: abv.as_i64(),
+    ..Default::default()
+  };
+  let reply = state
+    .upstream
+    .call(|channel| {
+      let req = req.clone();
+      async move { pb_client!(channel, ViewClient).view(req).await }
+    })
+    .await;
+  let reply = match reply {
+    Ok(reply) => reply.into_inner(),
+    Err(status)
+      if bili_error_code(&status).is_some_and(|code| UNAVAILABLE_CODES.contains(&code)) =>
+    {
+      let marked = con
+        .build_transaction()
+        .run::<_, diesel::result::Error, _>(|con| {
+          async move { db::mark_video_unavailable(con, abv.as_i64(), SystemTime::now()).await }
+            .scope_boxed()
+        })
+        .await
+        .with_context_into_app(|| format!("Failed to mark video {} unavailable", abv.av()))?;
+      if marked {
+        info!(
+          "Video {} became unavailable, {}",
+          abv.av(),
+          status.message()
+        );
+      }
+      return Err(app_err_custom!(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        RespCode::VIDEO_UNAVAILABLE,
+        "Video is deleted or invisible, aid = {}",
+        abv.av()
+      ));
+    },
+    Err(status) => {
+      return Err(status)
+        .with_context_into_app(|| format!("Unable to fetch video aid `{}`", abv.av()))
+    },
+  };
+
+  let archive: Archive = reply
+    .arc
+    .context("ViewReply malformed, no `arc` field")
+    .with_app_error(RespCode::BILI_CLIENT_ERROR)?;
+
+  let Some(aid) = Abv::new(archive.aid as u64) else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::BILI_CLIENT_ERROR,
+      "ViewReply malformed, aid == 0"
+    ));
+  };
+
+  let mut parts = Vec::with_capacity(reply.pages.len());
+
+  for page in reply.pages {
+    let page = page
+      .page
+      .context("ViewPage malformed, no `page` field")
+      .with_app_error(RespCode::BILI_CLIENT_ERROR)?;
+
+    if page.cid == 0 {
+      return Err(app_err!(
+        RespCode::BILI_CLIENT_ERROR,
+        "ViewPage.Page malformed, cid == 0"
+      ));
+    };
+
+    parts.push(db::VideoPart {
+      aid: aid.as_i64(),
+      cid: page.cid,
+      title: page.part,
+      duration: page.duration as f32,
+    });
+  }
+
+  let metadata = VideoMetadata {
+    video: db::Video {
+      aid: aid.as_i64(),
+      title: archive.title,
+      update_time: SystemTime::now(),
+      bvid_hash: Some(aid.bv_hash()),
+    },
+    parts,
+  };
+
+  let stored = &metadata;
+  let changes = con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move { db::store_video(con, &stored.video, &stored.parts).await }.scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| {
+      format!("Failed to insert video (aid `{}`) and its parts", aid.av())
+    })?;
+  if !changes.resized.is_empty() || !changes.removed.is_empty() {
+    info!(
+      "Parts of video {} changed, resized: {:?}, removed: {:?}",
+      aid.av(),
+      changes.resized,
+      changes.removed
+    );
+  }
+
+  Ok(Arc::new(metadata))
+}
+
+/// Refetches metadata of videos with segments submitted or listed recently, see
+/// [crate::config::MetadataRefreshConfig], returns the number of refreshed videos
+pub async fn refresh_videos(state: &App) -> anyhow::Result<usize> {
+  let config = &state.config.metadata_refresh;
+  let mut db_con = state.db_con().await?;
+
+  let now = SystemTime::now();
+  let since = now
+    .checked_sub(config.recent)
+    .unwrap_or(SystemTime::UNIX_EPOCH);
+  let refreshed_before = now
+    .checked_sub(state.config.video_cache.fresh_for)
+    .unwrap_or(now);
+  let aids = db::videos_to_refresh(&mut db_con, since, refreshed_before, config.batch as i64)
+    .await
+    .context("Failed to fetch videos to refresh")?;
+
+  let mut refreshed = 0;
+  for aid in aids {
+    let Some(abv) = Abv::new(aid as u64) else {
+      continue;
+    };
+    match refetch(state, &mut db_con, abv).await {
+      Ok(_) => refreshed += 1,
+      Err(err) => warn!(
+        "Failed to refresh metadata of video {}: {:?}",
+        abv.av(),
+        err.0
+      ),
+    }
+  }
+  Ok(refreshed)
+}
+
+/// Records in background that segments of parts `cids` were listed, at most once per
+/// [VIEW_RECORD_INTERVAL] for each part
+pub fn record_views(state: &Arc<App>, cids: impl IntoIterator<Item = i64>) {
+  let now = Instant::now();
+  let cids: Vec<i64> = cids
+    .into_iter()
+    .filter(|cid| {
+      let mut due = true;
+      state
+        .recorded_views
+        .entry(*cid)
+        .and_modify(|recorded| {
+          due = now.duration_since(*recorded) >= VIEW_RECORD_INTERVAL;
+          if due {
+            *recorded = now;
+          }
+        })
+        .or_insert(now);
+      due
+    })
+    .collect();
+  if cids.is_empty() {
+    return;
+  }
+
+  let state = Arc::clone(state);
+  tokio::spawn(async move {
+    state
+      .recorded_views
+      .retain(|_, recorded| now.duration_since(*recorded) < VIEW_RECORD_INTERVAL);
+
+    let result = match state.db_con().await {
+      Ok(mut db_con) => db::mark_viewed(&mut db_con, &cids, SystemTime::now())
+        .await
+        .context("Failed to record views"),
+      Err(err) => Err(err.into()),
+    };
+    if let Err(err) = result {
+      error!("{:?}", err);
+    }
+  });
+}
@@ -0,0 +1,41 @@
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct AuditLogReq {
+  #[serde(flatten)]
+  pub filter: db::AuditLogFilter,
+  /// Defaults to [AuditLogReq::DEFAULT_LIMIT], at most [AuditLogReq::MAX_LIMIT]
+  pub limit: Option<u32>,
+}
+
+impl AuditLogReq {
+  pub const DEFAULT_LIMIT: u32 = 50;
+  pub const MAX_LIMIT: u32 = 500;
+}
+
+#[derive(Serialize, Debug)]
+pub struct AuditLogData {
+  pub len: usize,
+  /// Newest first, pass the last id as `before` for the next page
+  pub logs: Vec<db::AuditLog>,
+}
+
+pub async fn audit_log(state: AppState, body: Json<AuditLogReq>) -> AppResult<Resp<AuditLogData>> {
+  let limit = body
+    .limit
+    .unwrap_or(AuditLogReq::DEFAULT_LIMIT)
+    .min(AuditLogReq::MAX_LIMIT);
+
+  let mut db_con = state.db_con().await?;
+  let logs = db::audit_logs(&mut db_con, &body.filter, limit as i64)
+    .await
+    .with_context_into_app(|| format!("Failed to fetch audit log, request: {:?}", &body.0))?;
+
+  Ok(
+    AuditLogData {
+      len: logs.len(),
+      logs,
+    }
+    .into(),
+  )
+}
@@ -0,0 +1,125 @@
+use std::{net::IpAddr, time::SystemTime};
+
+use ipnet::IpNet;
+
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct BanIpReq {
+  /// Address like `192.0.2.1`, or range like `2001:db8::/32`
+  pub ip: String,
+  pub banned: bool,
+  /// Shadow banned ranges can still write, but their users are shadow banned
+  #[serde(default)]
+  pub shadow: bool,
+  #[serde(default)]
+  pub reason: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BanIpData {
+  pub ip: String,
+  pub banned: bool,
+  pub shadow: bool,
+  /// Users shadow banned along with the range
+  pub shadow_banned_users: usize,
+}
+
+pub async fn ip_ban(
+  state: AppState,
+  Extension(actor): Extension<db::User>,
+  body: Json<BanIpReq>,
+) -> AppResult<Resp<BanIpData>> {
+  let Some(ip) = parse_ip_range(&body.ip) else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "Invalid ip or range `{}`",
+      body.ip
+    ));
+  };
+
+  let mut db_con = state.db_con().await?;
+  let body = &body.0;
+  let actor_id = actor.id;
+
+  let shadow_banned_users: Vec<Uuid> = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        let mut shadow_banned_users = Vec::new();
+
+        if body.banned {
+          let ban = db::Ban {
+            ip,
+            shadow: body.shadow,
+            reason: body.reason.clone(),
+            actor: actor_id,
+            time: SystemTime::now(),
+          };
+          diesel::insert_into(db::bans::table)
+            .values(&ban)
+            .on_conflict(db::bans::ip)
+            .do_update()
+            .set((
+              db::bans::shadow.eq(ban.shadow),
+              db::bans::reason.eq(&ban.reason),
+              db::bans::actor.eq(ban.actor),
+              db::bans::time.eq(ban.time),
+            ))
+            .execute(con)
+            .await?;
+
+          if body.shadow {
+            shadow_banned_users = db::shadow_ban_users_in(con, ip).await?;
+          }
+        } else {
+          diesel::delete(db::bans::table.find(ip))
+            .execute(con)
+            .await?;
+        }
+
+        let action = if body.banned {
+          db::AuditAction::IpBan
+        } else {
+          db::AuditAction::IpUnban
+        };
+        let mut entries = vec![db::NewAuditLog::new(Some(actor_id), action, ip)
+          .reason(&body.reason)
+          .detail(format!("shadow = {}", body.shadow))];
+        entries.extend(shadow_banned_users.iter().map(|id| {
+          db::NewAuditLog::new(Some(actor_id), db::AuditAction::UserShadowBan, id)
+            .reason(format!("in shadow banned ip range {ip}"))
+        }));
+        db::audit(con, &entries).await?;
+
+        Ok(shadow_banned_users)
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to update ban {ip}, request: {body:?}"))?;
+
+  info!(
+    "Moderator {} set ip {} banned = {}, shadow = {}, reason: {}",
+    actor.id, ip, body.banned, body.shadow, body.reason
+  );
+  Ok(
+    BanIpData {
+      ip: ip.to_string(),
+      banned: body.banned,
+      shadow: body.shadow,
+      shadow_banned_users: shadow_banned_users.len(),
+    }
+    .into(),
+  )
+}
+
+/// Host bits are cleared, since `CIDR` column rejects them
+fn parse_ip_range(raw: &str) -> Option<IpNet> {
+  raw
+    .parse::<IpNet>()
+    .or_else(|_| raw.parse::<IpAddr>().map(IpNet::from))
+    .ok()
+    .map(|ip| ip.trunc())
+}
@@ -0,0 +1,103 @@
+//! Moderation API, every route requires [db::UserRole::Moderator], see [crate::layer::admin_layer]
+
+use axum::{
+  routing::{get, post},
+  Router,
+};
+use diesel::{OptionalExtension, SelectableHelper};
+use diesel_async::AsyncPgConnection;
+
+use super::prelude::*;
+
+mod audit_log;
+mod ip_ban;
+mod queue;
+mod queue_resolve;
+mod segment_delete;
+mod segment_hide;
+mod segment_reset_votes;
+mod user_ban;
+mod user_role;
+mod video_lock;
+
+pub use audit_log::*;
+pub use ip_ban::*;
+pub use queue::*;
+pub use queue_resolve::*;
+pub use segment_delete::*;
+pub use segment_hide::*;
+pub use segment_reset_votes::*;
+pub use user_ban::*;
+pub use user_role::*;
+pub use video_lock::*;
+
+/// Routes to be nested under `/admin`
+pub fn router(state: Arc<App>) -> Router<Arc<App>> {
+  Router::new()
+    .route("/audit", get(audit_log))
+    .route("/segment/delete", post(admin_segment_delete))
+    .route("/segment/hide", post(segment_hide))
+    .route("/segment/reset_votes", post(segment_reset_votes))
+    .route("/video/lock", post(video_lock))
+    .route("/user/ban", post(user_ban))
+    .route("/user/role", post(user_role))
+    .route("/ip/ban", post(ip_ban))
+    .route("/queue", get(queue))
+    .route("/queue/resolve", post(queue_resolve))
+    .route_layer(axum::middleware::from_fn_with_state(state, admin_layer))
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdminUserData {
+  pub id: Uuid,
+  pub role: db::UserRole,
+  pub banned: bool,
+  pub shadow_banned: bool,
+}
+
+impl From<db::User> for AdminUserData {
+  fn from(user: db::User) -> Self {
+    Self {
+      id: user.id,
+      role: user.role,
+      banned: user.banned,
+      shadow_banned: user.shadow_banned,
+    }
+  }
+}
+
+/// Locks the user by public id for the rest of the transaction, `actor` can only manage users with lower roles
+async fn managed_user(
+  con: &mut AsyncPgConnection,
+  actor: &db::User,
+  id: Uuid,
+) -> diesel::QueryResult<AppResult<db::User>> {
+  let user: Option<db::User> = db::users::table
+    .find(id)
+    .select(db::User::as_select())
+    .for_update()
+    .first(con)
+    .await
+    .optional()?;
+
+  let Some(user) = user else {
+    return Ok(Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such user, id = {}",
+      id
+    )));
+  };
+
+  if user.role >= actor.role {
+    return Ok(Err(app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::PERMISSION_DENIED,
+      "Cannot manage user with role {:?}, id = {}",
+      user.role,
+      id
+    )));
+  }
+
+  Ok(Ok(user))
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct QueueReq {
+  /// Defaults to [QueueReq::DEFAULT_LIMIT], at most [QueueReq::MAX_LIMIT]
+  pub limit: Option<u32>,
+  #[serde(default)]
+  pub offset: u32,
+}
+
+impl QueueReq {
+  pub const DEFAULT_LIMIT: u32 = 50;
+  pub const MAX_LIMIT: u32 = 500;
+}
+
+#[derive(Serialize, Debug)]
+pub struct QueueItem {
+  pub segment: db::Segment,
+  pub aid: i64,
+  pub video_title: String,
+  pub part_title: String,
+  pub submitter_trust: f32,
+  /// Open reports, oldest first, empty for low confidence segments nobody reported
+  pub reports: Vec<db::Report>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QueueData {
+  pub len: usize,
+  /// Oldest segments first
+  pub items: Vec<QueueItem>,
+}
+
+/// Lists reported and low confidence segments waiting for moderation, see [queue_resolve]
+pub async fn queue(state: AppState, body: Json<QueueReq>) -> AppResult<Resp<QueueData>> {
+  let limit = body
+    .limit
+    .unwrap_or(QueueReq::DEFAULT_LIMIT)
+    .min(QueueReq::MAX_LIMIT);
+
+  let mut db_con = state.db_con().await?;
+  let segments = db::moderation_queue(&mut db_con, limit as i64, body.offset as i64)
+    .await
+    .with_context_into_app(|| {
+      format!("Failed to fetch moderation queue, request: {:?}", &body.0)
+    })?;
+
+  let ids: Vec<Uuid> = segments.iter().map(|(segment, ..)| segment.id).collect();
+  let reports = db::open_reports_of(&mut db_con, &ids)
+    .await
+    .with_context_into_app(|| "Failed to fetch reports of moderation queue")?;
+  let mut reports_of: HashMap<Uuid, Vec<db::Report>> = HashMap::with_capacity(ids.len());
+  for report in reports {
+    reports_of.entry(report.segment).or_default().push(report);
+  }
+
+  let items: Vec<QueueItem> = segments
+    .into_iter()
+    .map(
+      |(segment, aid, video_title, part_title, submitter_trust)| QueueItem {
+        reports: reports_of.remove(&segment.id).unwrap_or_default(),
+        segment,
+        aid,
+        video_title,
+        part_title,
+        submitter_trust,
+      },
+    )
+    .collect();
+
+  Ok(
+    QueueData {
+      len: items.len(),
+      items,
+    }
+    .into(),
+  )
+}
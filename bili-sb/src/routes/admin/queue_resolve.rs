@@ -0,0 +1,139 @@
+use std::time::SystemTime;
+
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct ResolveQueueReq {
+  pub segments: Vec<Uuid>,
+  pub action: QueueAction,
+  #[serde(default)]
+  pub reason: String,
+}
+
+impl ResolveQueueReq {
+  pub const MAX_SEGMENTS: usize = 500;
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueAction {
+  /// The segment is correct, it is no longer low confidence
+  Approve,
+  /// Hides the segment, same as [segment_hide]
+  Hide,
+  /// Removes the segment from the queue unchanged
+  Dismiss,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ResolveQueueData {
+  /// Ids of resolved segments, deleted or unknown ones are skipped
+  pub segments: Vec<Uuid>,
+  pub reports: usize,
+}
+
+/// Resolves items of the moderation queue in bulk, open reports on them are closed
+pub async fn queue_resolve(
+  state: AppState,
+  Extension(actor): Extension<db::User>,
+  body: Json<ResolveQueueReq>,
+) -> AppResult<Resp<ResolveQueueData>> {
+  if body.segments.len() > ResolveQueueReq::MAX_SEGMENTS {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "At most {} segments can be resolved at once",
+      ResolveQueueReq::MAX_SEGMENTS
+    ));
+  }
+
+  let mut db_con = state.db_con().await?;
+  let body = &body.0;
+  let actor_id = actor.id;
+
+  let data: ResolveQueueData = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        let now = SystemTime::now();
+        let target = db::segments::table.filter(
+          db::segments::id
+            .eq_any(&body.segments)
+            .and(db::segments::deleted_at.is_null()),
+        );
+        let segments: Vec<Uuid> = match body.action {
+          QueueAction::Approve => {
+            diesel::update(target)
+              .set((
+                db::segments::low_confidence.eq(false),
+                db::segments::reviewed_at.eq(now),
+              ))
+              .returning(db::segments::id)
+              .get_results(con)
+              .await?
+          },
+          QueueAction::Hide => {
+            diesel::update(target)
+              .set((
+                db::segments::hidden.eq(true),
+                db::segments::reviewed_at.eq(now),
+              ))
+              .returning(db::segments::id)
+              .get_results(con)
+              .await?
+          },
+          QueueAction::Dismiss => {
+            diesel::update(target)
+              .set(db::segments::reviewed_at.eq(now))
+              .returning(db::segments::id)
+              .get_results(con)
+              .await?
+          },
+        };
+
+        let reports: Vec<Uuid> = diesel::update(
+          db::reports::table.filter(
+            db::reports::segment
+              .eq_any(&segments)
+              .and(db::reports::resolved_at.is_null()),
+          ),
+        )
+        .set(db::reports::resolved_at.eq(now))
+        .returning(db::reports::segment)
+        .get_results(con)
+        .await?;
+
+        let action = match body.action {
+          QueueAction::Approve => db::AuditAction::SegmentApprove,
+          QueueAction::Hide => db::AuditAction::SegmentHide,
+          QueueAction::Dismiss => db::AuditAction::SegmentDismiss,
+        };
+        let entries: Vec<db::NewAuditLog> = segments
+          .iter()
+          .map(|segment| {
+            let resolved = reports.iter().filter(|report| *report == segment).count();
+            db::NewAuditLog::new(Some(actor_id), action, segment)
+              .reason(&body.reason)
+              .detail(format!("reports = {resolved}"))
+          })
+          .collect();
+        db::audit(con, &entries).await?;
+
+        Ok(ResolveQueueData {
+          segments,
+          reports: reports.len(),
+        })
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to resolve moderation queue, request: {body:?}"))?;
+
+  info!(
+    "Moderator {} resolved {} segments in moderation queue, action = {:?}",
+    actor.id,
+    data.segments.len(),
+    body.action
+  );
+  Ok(data.into())
+}
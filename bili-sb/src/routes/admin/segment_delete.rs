@@ -0,0 +1,64 @@
+use std::time::SystemTime;
+
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct AdminDeleteSegmentReq {
+  pub id: Uuid,
+  /// `false` to restore a deleted segment
+  pub deleted: bool,
+  #[serde(default)]
+  pub reason: String,
+}
+
+pub async fn admin_segment_delete(
+  state: AppState,
+  Extension(actor): Extension<db::User>,
+  body: Json<AdminDeleteSegmentReq>,
+) -> AppResult<Resp<db::Segment>> {
+  let mut db_con = state.db_con().await?;
+  let body = &body.0;
+  let actor_id = actor.id;
+
+  let segment: Option<db::Segment> = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        let (deleted_at, action) = if body.deleted {
+          (Some(SystemTime::now()), db::AuditAction::SegmentDelete)
+        } else {
+          (None, db::AuditAction::SegmentRestore)
+        };
+        let segment: Option<db::Segment> = diesel::update(db::segments::table.find(body.id))
+          .set(db::segments::deleted_at.eq(deleted_at))
+          .returning(db::Segment::as_returning())
+          .get_result(con)
+          .await
+          .optional()?;
+
+        if segment.is_some() {
+          let entry = db::NewAuditLog::new(Some(actor_id), action, body.id).reason(&body.reason);
+          db::audit(con, &[entry]).await?;
+        }
+        Ok(segment)
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to update segment, request: {body:?}"))?;
+
+  let Some(segment) = segment else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such segment, uuid = {}",
+      body.id
+    ));
+  };
+
+  info!(
+    "Moderator {} set segment {} deleted = {}",
+    actor.id, segment.id, body.deleted
+  );
+  Ok(segment.into())
+}
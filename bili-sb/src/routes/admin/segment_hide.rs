@@ -0,0 +1,61 @@
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct HideSegmentReq {
+  pub id: Uuid,
+  pub hidden: bool,
+  #[serde(default)]
+  pub reason: String,
+}
+
+pub async fn segment_hide(
+  state: AppState,
+  Extension(actor): Extension<db::User>,
+  body: Json<HideSegmentReq>,
+) -> AppResult<Resp<db::Segment>> {
+  let mut db_con = state.db_con().await?;
+  let body = &body.0;
+  let actor_id = actor.id;
+
+  let segment: Option<db::Segment> = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        let segment: Option<db::Segment> = diesel::update(db::segments::table.find(body.id))
+          .set(db::segments::hidden.eq(body.hidden))
+          .returning(db::Segment::as_returning())
+          .get_result(con)
+          .await
+          .optional()?;
+
+        if segment.is_some() {
+          let action = if body.hidden {
+            db::AuditAction::SegmentHide
+          } else {
+            db::AuditAction::SegmentUnhide
+          };
+          let entry = db::NewAuditLog::new(Some(actor_id), action, body.id).reason(&body.reason);
+          db::audit(con, &[entry]).await?;
+        }
+        Ok(segment)
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to update segment, request: {body:?}"))?;
+
+  let Some(segment) = segment else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such segment, uuid = {}",
+      body.id
+    ));
+  };
+
+  info!(
+    "Moderator {} set segment {} hidden = {}",
+    actor.id, segment.id, segment.hidden
+  );
+  Ok(segment.into())
+}
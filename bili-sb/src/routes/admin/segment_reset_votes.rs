@@ -0,0 +1,77 @@
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct ResetVotesReq {
+  pub id: Uuid,
+  #[serde(default)]
+  pub reason: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ResetVotesData {
+  pub id: Uuid,
+  /// Number of removed votes
+  pub removed: usize,
+}
+
+/// Removes every vote of a segment, e.g. after a brigade
+pub async fn segment_reset_votes(
+  state: AppState,
+  Extension(actor): Extension<db::User>,
+  body: Json<ResetVotesReq>,
+) -> AppResult<Resp<ResetVotesData>> {
+  let mut db_con = state.db_con().await?;
+  let body = &body.0;
+  let actor_id = actor.id;
+  let voting = &state.config.voting;
+
+  let removed: Option<usize> = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        let segment_exist: bool =
+          diesel::select(diesel::dsl::exists(db::segments::table.find(body.id)))
+            .get_result(con)
+            .await?;
+        if !segment_exist {
+          return Ok(None);
+        }
+
+        let removed = diesel::delete(db::votes::table.filter(db::votes::segment.eq(body.id)))
+          .execute(con)
+          .await?;
+
+        let entry = db::NewAuditLog::new(Some(actor_id), db::AuditAction::VoteReset, body.id)
+          .reason(&body.reason)
+          .detail(removed);
+        db::audit(con, &[entry]).await?;
+
+        refresh_visibility(con, body.id, voting).await?;
+        Ok(Some(removed))
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to reset votes, request: {body:?}"))?;
+
+  let Some(removed) = removed else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such segment, uuid = {}",
+      body.id
+    ));
+  };
+
+  info!(
+    "Moderator {} reset {} votes of segment {}",
+    actor.id, removed, body.id
+  );
+  Ok(
+    ResetVotesData {
+      id: body.id,
+      removed,
+    }
+    .into(),
+  )
+}
@@ -0,0 +1,67 @@
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct BanUserReq {
+  /// Public id
+  pub id: Uuid,
+  pub banned: bool,
+  /// Shadow banned users are not told, their submissions and votes are silently ignored
+  #[serde(default)]
+  pub shadow: bool,
+  #[serde(default)]
+  pub reason: String,
+}
+
+pub async fn user_ban(
+  state: AppState,
+  Extension(actor): Extension<db::User>,
+  body: Json<BanUserReq>,
+) -> AppResult<Resp<AdminUserData>> {
+  let mut db_con = state.db_con().await?;
+  let body = &body.0;
+  let actor = &actor;
+  let action = match (body.shadow, body.banned) {
+    (false, true) => db::AuditAction::UserBan,
+    (false, false) => db::AuditAction::UserUnban,
+    (true, true) => db::AuditAction::UserShadowBan,
+    (true, false) => db::AuditAction::UserShadowUnban,
+  };
+
+  let user: db::User = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        if let Err(err) = managed_user(con, actor, body.id).await? {
+          return Ok(Err(err));
+        }
+
+        let target = db::users::table.find(body.id);
+        let user: db::User = if body.shadow {
+          diesel::update(target)
+            .set(db::users::shadow_banned.eq(body.banned))
+            .returning(db::User::as_returning())
+            .get_result(con)
+            .await?
+        } else {
+          diesel::update(target)
+            .set(db::users::banned.eq(body.banned))
+            .returning(db::User::as_returning())
+            .get_result(con)
+            .await?
+        };
+
+        let entry = db::NewAuditLog::new(Some(actor.id), action, body.id).reason(&body.reason);
+        db::audit(con, &[entry]).await?;
+        Ok(Ok(user))
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to update user, request: {body:?}"))??;
+
+  info!(
+    "Moderator {} set user {} banned = {}, shadow banned = {}",
+    actor.id, user.id, user.banned, user.shadow_banned
+  );
+  Ok(AdminUserData::from(user).into())
+}
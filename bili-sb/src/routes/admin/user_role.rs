@@ -0,0 +1,61 @@
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct UserRoleReq {
+  /// Public id
+  pub id: Uuid,
+  pub role: db::UserRole,
+  #[serde(default)]
+  pub reason: String,
+}
+
+/// Promotes or demotes a user, the new role must be lower than the actor's
+pub async fn user_role(
+  state: AppState,
+  Extension(actor): Extension<db::User>,
+  body: Json<UserRoleReq>,
+) -> AppResult<Resp<AdminUserData>> {
+  if body.role >= actor.role {
+    return Err(app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::PERMISSION_DENIED,
+      "Cannot grant role {:?}",
+      body.role
+    ));
+  }
+
+  let mut db_con = state.db_con().await?;
+  let body = &body.0;
+  let actor = &actor;
+  let user: db::User = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        let previous = match managed_user(con, actor, body.id).await? {
+          Ok(previous) => previous,
+          Err(err) => return Ok(Err(err)),
+        };
+
+        let user: db::User = diesel::update(db::users::table.find(body.id))
+          .set(db::users::role.eq(body.role))
+          .returning(db::User::as_returning())
+          .get_result(con)
+          .await?;
+
+        let entry = db::NewAuditLog::new(Some(actor.id), db::AuditAction::UserRole, body.id)
+          .reason(&body.reason)
+          .detail(format!("{:?}", previous.role));
+        db::audit(con, &[entry]).await?;
+        Ok(Ok(user))
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to update user, request: {body:?}"))??;
+
+  info!(
+    "Moderator {} set user {} role = {:?}",
+    actor.id, user.id, user.role
+  );
+  Ok(AdminUserData::from(user).into())
+}
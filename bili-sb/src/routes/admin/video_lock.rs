@@ -0,0 +1,130 @@
+use std::{num::NonZeroU64, time::SystemTime};
+
+use diesel::{dsl::exists, PgExpressionMethods};
+
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct LockVideoReq {
+  #[serde(flatten)]
+  pub abv: Abv,
+  /// Omitted to lock every part of the video
+  pub cid: Option<NonZeroU64>,
+  /// Omitted to lock every category
+  pub category: Option<db::SegmentCategory>,
+  pub locked: bool,
+  #[serde(default)]
+  pub reason: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LockVideoData {
+  pub aid: u64,
+  pub cid: Option<NonZeroU64>,
+  pub category: Option<db::SegmentCategory>,
+  pub locked: bool,
+}
+
+pub async fn video_lock(
+  state: AppState,
+  Extension(actor): Extension<db::User>,
+  body: Json<LockVideoReq>,
+) -> AppResult<Resp<LockVideoData>> {
+  let mut db_con = state.db_con().await?;
+  let aid = body.abv.as_i64();
+  let cid = body.cid.map(|cid| cid.get() as i64);
+
+  if body.locked {
+    let video_exist: bool = match cid {
+      Some(cid) => {
+        diesel::select(exists(
+          db::video_parts::table.filter(
+            db::video_parts::cid
+              .eq(cid)
+              .and(db::video_parts::aid.eq(aid)),
+          ),
+        ))
+        .get_result(&mut db_con)
+        .await
+      },
+      None => {
+        diesel::select(exists(db::videos::table.find(aid)))
+          .get_result(&mut db_con)
+          .await
+      },
+    }
+    .with_context_into_app(|| format!("Failed to fetch video, aid = {aid}, cid = {cid:?}"))?;
+    if !video_exist {
+      return Err(app_err_custom!(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        RespCode::INVALID_PARAMS,
+        "No segment has been submitted to this video, aid = {}, cid = {:?}",
+        aid,
+        cid
+      ));
+    }
+  }
+
+  let body = &body.0;
+  let actor_id = actor.id;
+  db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        let (updated, action) = if body.locked {
+          let lock = db::NewLock {
+            aid,
+            cid,
+            category: body.category,
+            locker: actor_id,
+            time: SystemTime::now(),
+          };
+          let inserted = diesel::insert_into(db::locks::table)
+            .values(&lock)
+            .on_conflict_do_nothing()
+            .execute(con)
+            .await?;
+          (inserted, db::AuditAction::VideoLock)
+        } else {
+          let deleted = diesel::delete(
+            db::locks::table
+              .filter(db::locks::aid.eq(aid))
+              .filter(db::locks::cid.is_not_distinct_from(cid))
+              .filter(db::locks::category.is_not_distinct_from(body.category)),
+          )
+          .execute(con)
+          .await?;
+          (deleted, db::AuditAction::VideoUnlock)
+        };
+
+        if updated != 0 {
+          let entry = db::NewAuditLog::new(Some(actor_id), action, aid)
+            .reason(&body.reason)
+            .detail(format!("cid = {:?}, category = {:?}", cid, body.category));
+          db::audit(con, &[entry]).await?;
+        }
+        Ok(())
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to update lock, request: {body:?}"))?;
+
+  info!(
+    "Moderator {} set video {} locked = {}, cid = {:?}, category = {:?}",
+    actor.id,
+    body.abv.av(),
+    body.locked,
+    cid,
+    body.category
+  );
+  Ok(
+    LockVideoData {
+      aid: body.abv.av(),
+      cid: body.cid,
+      category: body.category,
+      locked: body.locked,
+    }
+    .into(),
+  )
+}
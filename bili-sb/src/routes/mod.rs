@@ -1,24 +1,202 @@
+pub mod admin;
 mod pow;
 mod segment_create;
+mod segment_delete;
+mod segment_edit;
 mod segment_list;
+mod segment_list_hash;
+mod segment_report;
+mod segment_revisions;
+mod segment_suggestion;
 mod segment_vote;
 mod user_create;
+mod user_info;
 
 pub use pow::*;
 pub use segment_create::*;
+pub use segment_delete::*;
+pub use segment_edit::*;
 pub use segment_list::*;
+pub use segment_list_hash::*;
+pub use segment_report::*;
+pub use segment_revisions::*;
+pub use segment_suggestion::*;
 pub use segment_vote::*;
 pub use user_create::*;
+pub use user_info::*;
 
-/// Prelude for `routes` mod
-mod prelude {
+use std::collections::HashMap;
+
+use diesel_async::AsyncPgConnection;
+use ipnet::IpNet;
+
+use self::prelude::*;
+use crate::config::VotingConfig;
+
+/// Resolves the user by private secret for write operations, banned users are rejected
+pub async fn authenticate(con: &mut PooledPgCon<'_>, secret: &Uuid) -> AppResult<db::User> {
+  use diesel::{OptionalExtension, SelectableHelper};
+
+  let id = db::User::public_id(secret);
+  let user: Option<db::User> = db::users::table
+    .find(id)
+    .select(db::User::as_select())
+    .first(con)
+    .await
+    .optional()
+    .with_context_into_app(|| format!("Failed to fetch user, id = {id}"))?;
+
+  let Some(user) = user else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such user, id = {}",
+      id
+    ));
+  };
+
+  if user.banned {
+    return Err(app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::USER_BANNED,
+      "User is banned, id = {}",
+      id
+    ));
+  }
+
+  Ok(user)
+}
+
+/// Shadow bans the user if the request comes from a shadow banned IP range, see [ban_layer]
+pub async fn shadow_ban_by_ip(
+  con: &mut PooledPgCon<'_>,
+  user: &mut db::User,
+  ip_shadow_banned: Option<Extension<IpShadowBanned>>,
+) -> AppResult<()> {
+  if ip_shadow_banned.is_none() || user.shadow_banned {
+    return Ok(());
+  }
+  let id = user.id;
+  con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move { db::shadow_ban_user(con, id).await }.scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to shadow ban user {}", user.id))?;
+  user.shadow_banned = true;
+  Ok(())
+}
+
+/// Rejects changes by non-VIPs to segments of `category` in part `cid` of video `aid`, if the
+/// scope is locked
+///
+/// Locks of whole videos keep [RespCode::VIDEO_LOCKED], narrower ones are [RespCode::SCOPE_LOCKED]
+pub async fn check_lock(
+  con: &mut PooledPgCon<'_>,
+  user: &db::User,
+  aid: i64,
+  cid: i64,
+  category: db::SegmentCategory,
+) -> AppResult<()> {
+  if user.role >= db::UserRole::Vip {
+    return Ok(());
+  }
+
+  let lock = db::lock_of(con, aid, cid, category)
+    .await
+    .with_context_into_app(|| format!("Failed to fetch lock of aid {aid}, cid {cid}"))?;
+  match lock {
+    None => Ok(()),
+    Some(lock) if lock.cid.is_none() && lock.category.is_none() => Err(app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::VIDEO_LOCKED,
+      "Video is locked, aid = {}",
+      aid
+    )),
+    Some(lock) => Err(app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::SCOPE_LOCKED,
+      "Segments are locked, aid = {}, cid = {:?}, category = {:?}",
+      aid,
+      lock.cid,
+      lock.category
+    )),
+  }
+}
+
+/// Re-evaluates [db::Segment::auto_hidden] and [db::Segment::low_confidence] from votes of
+/// `segment`, changes of `auto_hidden` are recorded in audit log
+///
+/// Runs in the transaction changing the votes, so that a visibility change is never left
+/// unaudited
+pub async fn refresh_visibility(
+  con: &mut AsyncPgConnection,
+  segment: Uuid,
+  config: &VotingConfig,
+) -> diesel::QueryResult<()> {
+  let votes = db::votes_with_roles(con, segment).await?;
+
+  // votes of the same type from one network count once, with the highest weight
+  let network = config.network();
+  let mut weights: HashMap<(IpNet, db::VoteType), i64> = HashMap::with_capacity(votes.len());
+  for (type_, role, trust, ip) in votes {
+    let weight = weights.entry((network.network_of(ip), type_)).or_default();
+    *weight = config.weight(role, trust).max(*weight);
+  }
+  let score: i64 = weights
+    .iter()
+    .map(|((_, type_), weight)| match type_ {
+      db::VoteType::Up => *weight,
+      db::VoteType::Down => -*weight,
+    })
+    .sum();
+  let votes = weights.values().filter(|weight| **weight > 0).count();
+  let hide = config.should_hide(score, votes);
+
+  if score > 0 {
+    diesel::update(
+      db::segments::table
+        .find(segment)
+        .filter(db::segments::low_confidence.eq(true)),
+    )
+    .set(db::segments::low_confidence.eq(false))
+    .execute(con)
+    .await?;
+  }
+
+  let updated = diesel::update(
+    db::segments::table
+      .find(segment)
+      .filter(db::segments::auto_hidden.ne(hide)),
+  )
+  .set(db::segments::auto_hidden.eq(hide))
+  .execute(con)
+  .await?;
+
+  if updated != 0 {
+    let action = if hide {
+      db::AuditAction::SegmentAutoHide
+    } else {
+      db::AuditAction::SegmentAutoUnhide
+    };
+    let entry = db::NewAuditLog::new(None, action, segment)
+      .detail(format!("score = {score}, votes = {votes}"));
+    db::audit(con, &[entry]).await?;
+    debug!("Segment {} auto hidden = {}", segment, hide);
+  }
+  Ok(())
+}
+
+/// Prelude for `routes` mod, also used by [crate::sponsorblock]
+pub(crate) mod prelude {
   pub use anyhow::Context;
-  pub use axum::Json;
+  pub use axum::{Extension, Json};
   pub use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl};
   pub use diesel_async::{scoped_futures::ScopedFutureExt, RunQueryDsl};
-  pub use log::{debug, error, info, warn};
+  pub use log::{debug, info};
   pub use serde::{Deserialize, Serialize};
   pub use tokio::spawn;
 
-  pub use crate::{client::*, error::*, state::*, *};
+  pub use crate::{error::*, state::*, *};
 }
@@ -1,184 +1,346 @@
 use std::{num::NonZeroU64, time::SystemTime};
 
-use super::prelude::*;
+use diesel::OptionalExtension;
+use ipnet::IpNet;
 
-use bilibili::app::archive::v1::Arc as Archive;
+use super::prelude::*;
+use crate::{config::DuplicateAction, metadata::video_metadata};
 
 #[derive(Deserialize, Debug)]
 pub struct CreateSegmentReq {
-  pub start: f32,
-  pub end: f32,
+  #[serde(default)]
+  pub category: db::SegmentCategory,
+  #[serde(default)]
+  pub action_type: db::ActionType,
+  /// Omitted for [db::ActionType::Full]
+  pub start: Option<f32>,
+  /// Omitted for [db::ActionType::Full], optional for [db::ActionType::Poi]
+  pub end: Option<f32>,
   #[serde(flatten)]
   pub abv: Abv,
   pub cid: NonZeroU64,
+  /// Private secret of the submitter
   pub submitter: Uuid,
 }
 
-/// Returns AppResult<Resp<db::Segment>>
-///
-/// See also: [db::Segment]
-pub async fn segment_create(
-  state: AppState,
-  ip: SecureClientIp,
-  body: Json<CreateSegmentReq>,
-) -> AppResult<Response> {
-  let bili = state.bili().await?;
-  let mut view = pb_client!(bili, ViewClient);
-  let mut db_con: PooledPgCon = state.db_con_owned().await?;
+impl CreateSegmentReq {
+  fn range(&self) -> AppResult<(f32, f32)> {
+    segment_range(self.category, self.action_type, self.start, self.end)
+  }
+}
 
-  let user: db::User = match db::users::table
-    .filter(db::users::id.eq(body.submitter))
-    .first(&mut db_con)
-    .await
-  {
-    Ok(user) => user,
-    Err(error) => match error {
-      diesel::result::Error::NotFound => {
-        return Err(app_err_custom!(
-          StatusCode::UNPROCESSABLE_ENTITY,
-          RespCode::INVALID_PARAMS,
-          "No such user, uuid = {}",
-          body.submitter
-        ))
-      },
-      err => Err(err).context_into_app("Failed to fetch user")?,
-    },
-  };
+/// Validates `start` and `end` against `action_type`, returns the range to store
+pub(super) fn segment_range(
+  category: db::SegmentCategory,
+  action_type: db::ActionType,
+  start: Option<f32>,
+  end: Option<f32>,
+) -> AppResult<(f32, f32)> {
+  use db::ActionType as A;
 
-  if body.start > body.end {
+  if let Some(start) = start.filter(|start| *start < 0.0) {
     return Err(app_err_custom!(
       StatusCode::UNPROCESSABLE_ENTITY,
       RespCode::INVALID_PARAMS,
-      "segment start is larger than end, {} > {}",
-      body.start,
-      body.end
+      "segment start is negative, {}",
+      start
     ));
-  };
-
-  let reply = view
-    .view(view::ViewReq {
-      aid: body.abv.as_i64(),
-      ..Default::default()
-    })
-    .await
-    .with_context_into_app(|| format!("Unable to fetch video aid `{}`", body.abv.av()))?
-    .into_inner();
-
-  let archive: Archive = reply
-    .arc
-    .context("ViewReply malformed, no `arc` field")
-    .with_app_error(RespCode::BILI_CLIENT_ERROR)?;
+  }
 
-  let Some(aid) = Abv::new(archive.aid as u64) else {
-    return Err(app_err_custom!(
+  match (action_type, start, end) {
+    (A::Skip | A::Mute, Some(start), Some(end)) if start < end => Ok((start, end)),
+    (A::Skip | A::Mute, Some(start), Some(end)) => Err(app_err_custom!(
       StatusCode::UNPROCESSABLE_ENTITY,
-      RespCode::BILI_CLIENT_ERROR,
-      "ViewReply malformed, aid == 0"
-    ));
-  };
-
-  let mut parts = Vec::with_capacity(reply.pages.len());
-
-  for page in reply.pages {
-    let page = page
-      .page
-      .context("ViewPage malformed, no `page` field")
-      .with_app_error(RespCode::BILI_CLIENT_ERROR)?;
-
-    if page.cid == 0 {
-      return Err(app_err!(
-        RespCode::BILI_CLIENT_ERROR,
-        "ViewPage.Page malformed, cid == 0"
-      ));
-    };
-
-    parts.push(db::VideoPart {
-      aid: aid.as_i64(),
-      cid: page.cid,
-      title: page.part,
-      duration: page.duration as f32,
-    });
+      RespCode::INVALID_PARAMS,
+      "segment start is not less than end, {} >= {}",
+      start,
+      end
+    )),
+    (A::Skip | A::Mute, ..) => Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "both `start` and `end` are required for action type {:?}",
+      action_type
+    )),
+    (A::Poi, Some(start), None) => Ok((start, start)),
+    (A::Poi, Some(start), Some(end)) if start == end => Ok((start, end)),
+    (A::Poi, ..) => Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "point of interest requires `start`, and `end` must be omitted or equal to it"
+    )),
+    (A::Full, None, None) => match category {
+      db::SegmentCategory::Sponsor | db::SegmentCategory::SelfPromo => Ok((0.0, 0.0)),
+      category => Err(app_err_custom!(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        RespCode::INVALID_PARAMS,
+        "category {:?} cannot label a full video",
+        category
+      )),
+    },
+    (A::Full, ..) => Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "full video label does not accept `start` or `end`"
+    )),
   }
+}
 
-  if !parts.iter().any(|page| page.cid == body.cid.get() as i64) {
+/// Rejects segments ending after the video part
+pub(super) fn check_duration(end: f32, duration: f32) -> AppResult<()> {
+  if end > duration {
     return Err(app_err_custom!(
       StatusCode::UNPROCESSABLE_ENTITY,
       RespCode::INVALID_PARAMS,
-      "cid is not valid {}",
-      body.cid
+      "segment end exceeds the video part duration, {} > {}",
+      end,
+      duration
     ));
   }
+  Ok(())
+}
 
-  let video = db::Video {
-    aid: aid.as_i64(),
-    title: archive.title,
-    update_time: SystemTime::now(),
-  };
+#[derive(Serialize, Debug)]
+pub struct DuplicateSegmentData {
+  /// Id of the existing segment
+  pub id: Uuid,
+  /// Whether the existing segment was upvoted on behalf of the submitter,
+  /// a previous downvote of theirs is kept rather than flipped
+  pub upvoted: bool,
+}
+
+/// Finds the existing segment overlapping the most with the submission, see [crate::config::SegmentDuplicateConfig]
+async fn duplicate_of(
+  con: &mut PooledPgCon<'_>,
+  body: &CreateSegmentReq,
+  range: (f32, f32),
+  config: &Config,
+) -> AppResult<Option<db::SegmentWithVote>> {
+  let min_ratio = config.segment_duplicate.overlap_ratio;
+  if min_ratio > 1.0 {
+    return Ok(None);
+  }
 
-  let new_user = db::User {
-    last_operation_time: Some(SystemTime::now()),
-    last_operation_ip: Some(ip.0.into()),
-    ..user.clone()
+  let filter = db::SegmentFilter {
+    categories: vec![body.category],
+    action_types: vec![body.action_type],
   };
+  let cid = body.cid.get() as i64;
+  let existing = db::segments_related_to_cid(con, cid, &filter, config.voting.network())
+    .await
+    .with_context_into_app(|| format!("Failed to fetch segments of cid {cid}"))?;
 
-  let segment = Arc::new(db::Segment {
-    id: Uuid::new_v4(),
-    cid: body.cid.get() as i64,
-    start: body.start,
-    end: body.end,
-    submitter: user.id,
-    submitter_ip: ip.0.into(),
-    time: SystemTime::now(),
-  });
+  Ok(
+    existing
+      .into_iter()
+      .map(|segment| (overlap_ratio(range, (segment.start, segment.end)), segment))
+      .filter(|(ratio, _)| *ratio >= min_ratio)
+      .max_by(|(a, _), (b, _)| a.total_cmp(b))
+      .map(|(_, segment)| segment),
+  )
+}
+
+async fn duplicated(
+  con: &mut PooledPgCon<'_>,
+  user: &db::User,
+  user_ip: IpNet,
+  duplicate: db::SegmentWithVote,
+  config: &Config,
+) -> AppResult<Response> {
+  let upvote = config.segment_duplicate.action == DuplicateAction::Upvote;
 
-  let db_segment = Arc::clone(&segment);
-  spawn(async move {
-    let update_result = db_con
+  // submitting the same segment again does not count as a vote on their own
+  let submitter: Uuid = db::segments::table
+    .find(duplicate.id)
+    .select(db::segments::submitter)
+    .first(con)
+    .await
+    .with_context_into_app(|| format!("Failed to fetch segment {}", duplicate.id))?;
+  let upvoted = if upvote && submitter != user.id {
+    let vote = db::Vote {
+      segment: duplicate.id,
+      type_: db::VoteType::Up,
+      voter: user.id,
+      voter_ip: user_ip,
+      time: SystemTime::now(),
+    };
+    let db_vote = &vote;
+    let voting = &config.voting;
+    con
       .build_transaction()
       .run::<_, diesel::result::Error, _>(|con| {
         async move {
-          diesel::insert_into(db::users::table)
-            .values(&new_user)
-            .on_conflict(db::users::id)
-            .do_update()
-            .set(&new_user)
-            .execute(con)
-            .await?;
+          let existing: Option<db::VoteType> = db::votes::table
+            .find((db_vote.segment, db_vote.voter))
+            .select(db::votes::type_)
+            .for_update()
+            .first(con)
+            .await
+            .optional()?;
+          if !implicit_upvote(existing) {
+            return Ok(false);
+          }
 
-          diesel::insert_into(db::videos::table)
-            .values(&video)
-            .on_conflict(db::videos::aid)
-            .do_update()
-            .set(&video)
+          diesel::update(db::users::table.find(db_vote.voter))
+            .set((
+              db::users::last_operation_time.eq(db_vote.time),
+              db::users::last_operation_ip.eq(db_vote.voter_ip),
+            ))
             .execute(con)
             .await?;
 
-          for part in parts.iter() {
-            diesel::insert_into(db::video_parts::table)
-              .values(part)
-              .on_conflict(db::video_parts::cid)
-              .do_update()
-              .set(part)
-              .execute(con)
-              .await?;
-          }
-
-          diesel::insert_into(db::segments::table)
-            .values(db_segment.as_ref())
+          diesel::insert_into(db::votes::table)
+            .values(db_vote)
+            .on_conflict((db::votes::segment, db::votes::voter))
+            .do_update()
+            .set(db_vote)
             .execute(con)
             .await?;
 
-          Ok(())
+          refresh_visibility(con, db_vote.segment, voting).await?;
+          Ok(true)
         }
         .scope_boxed()
       })
       .await
-      .with_context(|| format!("Failed to insert video (aid `{}`) and its parts", aid.av()));
+      .with_context_into_app(|| format!("Failed to upsert votes, vote: {:?}", &vote))?
+  } else {
+    false
+  };
+
+  let resp = Resp {
+    code: RespCode::SEGMENT_DUPLICATED,
+    message: Some(format!("Duplicate of segment {}", duplicate.id)),
+    data: Some(DuplicateSegmentData {
+      id: duplicate.id,
+      upvoted,
+    }),
+  };
+  let http_code = if upvote {
+    StatusCode::OK
+  } else {
+    StatusCode::CONFLICT
+  };
+  Ok((http_code, resp).into_response())
+}
+
+/// Returns AppResult<Resp<db::Segment>>
+///
+/// See also: [db::Segment]
+pub async fn segment_create(
+  state: AppState,
+  ip: SecureClientIp,
+  ip_shadow_banned: Option<Extension<IpShadowBanned>>,
+  body: Json<CreateSegmentReq>,
+) -> AppResult<Response> {
+  let mut db_con: PooledPgCon = state.db_con().await?;
+
+  let mut user = authenticate(&mut db_con, &body.submitter).await?;
+  shadow_ban_by_ip(&mut db_con, &mut user, ip_shadow_banned).await?;
 
-    if let Err(err) = update_result {
-      error!("{:?}", err);
-    }
-  });
+  check_lock(
+    &mut db_con,
+    &user,
+    body.abv.as_i64(),
+    body.cid.get() as i64,
+    body.category,
+  )
+  .await?;
+
+  let (start, end) = body.range()?;
+
+  let cid = body.cid.get() as i64;
+  let metadata = video_metadata(&state, &mut db_con, body.abv, cid).await?;
+  let Some(part) = metadata.part(cid) else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "cid is not valid {}",
+      body.cid
+    ));
+  };
+  let video_duration = part.duration;
+  check_duration(end, video_duration)?;
+
+  // only a submission which would be accepted turns into an upvote
+  if let Some(duplicate) =
+    duplicate_of(&mut db_con, &body, (start, end), state.config.as_ref()).await?
+  {
+    return duplicated(
+      &mut db_con,
+      &user,
+      ip.0.into(),
+      duplicate,
+      state.config.as_ref(),
+    )
+    .await;
+  }
+
+  let user_ip: IpNet = ip.0.into();
+  let segment = db::Segment {
+    id: Uuid::new_v4(),
+    cid,
+    category: body.category,
+    action_type: body.action_type,
+    start,
+    end,
+    submitter: user.id,
+    submitter_ip: user_ip,
+    time: SystemTime::now(),
+    hidden: false,
+    deleted_at: None,
+    video_duration,
+    auto_hidden: false,
+    low_confidence: user.role < db::UserRole::Vip
+      && user.trust < state.config.trust.low_confidence_below,
+    reviewed_at: None,
+  };
+
+  // committed before responding, so that the returned segment always exists
+  let db_segment = &segment;
+  db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        // only touch these columns, so that a concurrent ban or role change is kept
+        diesel::update(db::users::table.find(user.id))
+          .set((
+            db::users::last_operation_time.eq(SystemTime::now()),
+            db::users::last_operation_ip.eq(user_ip),
+          ))
+          .execute(con)
+          .await?;
+
+        diesel::insert_into(db::segments::table)
+          .values(db_segment)
+          .execute(con)
+          .await?;
+
+        Ok(())
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| {
+      format!(
+        "Failed to insert segment {} of video (aid `{}`)",
+        segment.id,
+        body.abv.av()
+      )
+    })?;
+
+  Ok(Resp::new_success(segment).into_response())
+}
+
+/// Whether a duplicate submission upvotes the existing segment given the submitter's current vote on it,
+/// an explicit downvote outweighs resubmitting the segment
+fn implicit_upvote(existing: Option<db::VoteType>) -> bool {
+  existing != Some(db::VoteType::Down)
+}
 
-  Ok(Resp::new_success(segment.as_ref()).into_response())
+#[test]
+fn implicit_upvote_test() {
+  assert!(implicit_upvote(None));
+  assert!(implicit_upvote(Some(db::VoteType::Up)));
+  assert!(!implicit_upvote(Some(db::VoteType::Down)));
 }
@@ -0,0 +1,66 @@
+use std::time::SystemTime;
+
+use diesel::{OptionalExtension, SelectableHelper};
+
+use super::prelude::*;
+
+#[derive(Deserialize, Debug)]
+pub struct DeleteSegmentReq {
+  pub id: Uuid,
+  /// Private secret of the submitter
+  pub submitter: Uuid,
+}
+
+/// Withdraws a segment by its submitter, the segment is soft deleted and can be restored by moderators
+pub async fn segment_delete(
+  state: AppState,
+  body: Json<DeleteSegmentReq>,
+) -> AppResult<Resp<db::Segment>> {
+  let mut db_con = state.db_con().await?;
+  let user = authenticate(&mut db_con, &body.submitter).await?;
+
+  let body = &body.0;
+  let user_id = user.id;
+  let segment: Option<db::Segment> = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        let segment: Option<db::Segment> = diesel::update(
+          db::segments::table.filter(
+            db::segments::id
+              .eq(body.id)
+              .and(db::segments::submitter.eq(user_id))
+              .and(db::segments::deleted_at.is_null()),
+          ),
+        )
+        .set(db::segments::deleted_at.eq(SystemTime::now()))
+        .returning(db::Segment::as_returning())
+        .get_result(con)
+        .await
+        .optional()?;
+
+        if segment.is_some() {
+          let entry = db::NewAuditLog::new(Some(user_id), db::AuditAction::SegmentDelete, body.id)
+            .reason("withdrawn by submitter");
+          db::audit(con, &[entry]).await?;
+        }
+        Ok(segment)
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to delete segment, uuid = {}", body.id))?;
+
+  let Some(segment) = segment else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such segment submitted by user {}, uuid = {}",
+      user.id,
+      body.id
+    ));
+  };
+
+  debug!("User {} withdrew segment {}", user.id, segment.id);
+  Ok(segment.into())
+}
@@ -0,0 +1,260 @@
+use std::time::SystemTime;
+
+use diesel::{OptionalExtension, SelectableHelper};
+use diesel_async::AsyncPgConnection;
+use ipnet::IpNet;
+
+use super::{
+  prelude::*,
+  segment_create::{check_duration, segment_range},
+};
+use crate::config::VoteCarryOver;
+
+#[derive(Deserialize, Debug)]
+pub struct EditSegmentReq {
+  pub id: Uuid,
+  /// Omitted to keep the current one
+  pub category: Option<db::SegmentCategory>,
+  /// Omitted to keep the current one, not accepted for [db::ActionType::Full]
+  pub start: Option<f32>,
+  /// Omitted to keep the current one, not accepted for [db::ActionType::Full]
+  pub end: Option<f32>,
+  /// Private secret of the submitter, or of a moderator
+  pub editor: Uuid,
+}
+
+/// Edits range or category of a segment, the previous version is kept in [db::SegmentRevision]
+///
+/// Votes are reset or carried over according to [crate::config::SegmentEditConfig]
+pub async fn segment_edit(
+  state: AppState,
+  ip: SecureClientIp,
+  ip_shadow_banned: Option<Extension<IpShadowBanned>>,
+  body: Json<EditSegmentReq>,
+) -> AppResult<Resp<db::Segment>> {
+  let mut db_con = state.db_con().await?;
+
+  let mut user = authenticate(&mut db_con, &body.editor).await?;
+  shadow_ban_by_ip(&mut db_con, &mut user, ip_shadow_banned).await?;
+
+  let segment: Option<(db::Segment, i64, f32)> = db::segments::table
+    .inner_join(db::video_parts::table)
+    .filter(db::segments::id.eq(body.id))
+    .select((
+      db::Segment::as_select(),
+      db::video_parts::aid,
+      db::video_parts::duration,
+    ))
+    .first(&mut db_con)
+    .await
+    .optional()
+    .with_context_into_app(|| format!("Failed to fetch segment, uuid = {}", body.id))?;
+  let Some((segment, aid, video_duration)) = segment else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such segment, uuid = {}",
+      body.id
+    ));
+  };
+  check_editable(&segment, &user)?;
+
+  let category = body.category.unwrap_or(segment.category);
+  // moving a segment into or out of a locked category is a change to both
+  check_lock(&mut db_con, &user, aid, segment.cid, segment.category).await?;
+  if category != segment.category {
+    check_lock(&mut db_con, &user, aid, segment.cid, category).await?;
+  }
+
+  let (start, end) = match segment.action_type {
+    db::ActionType::Full => (body.start, body.end),
+    db::ActionType::Poi => (body.start.or(Some(segment.start)), body.end),
+    db::ActionType::Skip | db::ActionType::Mute => (
+      body.start.or(Some(segment.start)),
+      body.end.or(Some(segment.end)),
+    ),
+  };
+  let (start, end) = segment_range(category, segment.action_type, start, end)?;
+  check_duration(end, video_duration)?;
+
+  if category == segment.category && start == segment.start && end == segment.end {
+    return Ok(segment.into());
+  }
+
+  let editor_ip: IpNet = ip.0.into();
+  let carry_over = state.config.segment_edit.votes;
+  let voting = &state.config.voting;
+  let body = &body.0;
+  let user = &user;
+
+  let edit = SegmentEdit {
+    id: body.id,
+    category,
+    start,
+    end,
+    video_duration,
+  };
+
+  let checked = &segment;
+
+  let (segment, votes_reset): (db::Segment, bool) = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        let locked: db::Segment = db::segments::table
+          .find(edit.id)
+          .select(db::Segment::as_select())
+          .for_update()
+          .first(con)
+          .await?;
+        if let Err(err) = check_editable(&locked, user) {
+          return Ok(Err(err));
+        }
+        // the lock and range checks above were done against the version read before the transaction
+        if (locked.category, locked.start, locked.end)
+          != (checked.category, checked.start, checked.end)
+        {
+          return Ok(Err(app_err_custom!(
+            StatusCode::CONFLICT,
+            RespCode::INVALID_PARAMS,
+            "Segment {} was edited concurrently",
+            locked.id
+          )));
+        }
+
+        let (old, segment, votes_reset) =
+          apply_edit(con, &edit, user.id, editor_ip, carry_over).await?;
+
+        if old.submitter != user.id {
+          let entry = db::NewAuditLog::new(Some(user.id), db::AuditAction::SegmentEdit, old.id)
+            .detail(format!(
+              "{:?} {}..{}, votes reset = {}",
+              old.category, old.start, old.end, votes_reset
+            ));
+          db::audit(con, &[entry]).await?;
+        }
+
+        diesel::update(db::users::table.find(user.id))
+          .set((
+            db::users::last_operation_time.eq(SystemTime::now()),
+            db::users::last_operation_ip.eq(editor_ip),
+          ))
+          .execute(con)
+          .await?;
+
+        if votes_reset {
+          refresh_visibility(con, segment.id, voting).await?;
+        }
+
+        Ok(Ok((segment, votes_reset)))
+      }
+      .scope_boxed()
+    })
+    .await
+    .with_context_into_app(|| format!("Failed to edit segment, request: {body:?}"))??;
+
+  debug!(
+    "User {} edited segment {}, votes reset = {}",
+    user.id, segment.id, votes_reset
+  );
+  Ok(segment.into())
+}
+
+/// Only the submitter or a moderator can edit a segment, segments hidden by a moderator only by moderators
+fn check_editable(segment: &db::Segment, user: &db::User) -> AppResult<()> {
+  if segment.deleted_at.is_some() {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such segment, uuid = {}",
+      segment.id
+    ));
+  }
+  if user.role >= db::UserRole::Moderator {
+    return Ok(());
+  }
+
+  if segment.submitter != user.id {
+    return Err(app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::PERMISSION_DENIED,
+      "Only the submitter or a moderator can edit segment {}",
+      segment.id
+    ));
+  }
+  if segment.hidden {
+    return Err(app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::PERMISSION_DENIED,
+      "Segment {} is hidden by a moderator",
+      segment.id
+    ));
+  }
+  Ok(())
+}
+
+pub(super) struct SegmentEdit {
+  pub id: Uuid,
+  pub category: db::SegmentCategory,
+  pub start: f32,
+  pub end: f32,
+  /// Current duration of the video part, which the range is validated against
+  pub video_duration: f32,
+}
+
+/// Applies a validated edit in a transaction, the previous version is kept in
+/// [db::SegmentRevision], returns the previous and the new version and whether votes were reset
+pub(super) async fn apply_edit(
+  con: &mut AsyncPgConnection,
+  edit: &SegmentEdit,
+  editor: Uuid,
+  editor_ip: IpNet,
+  carry_over: VoteCarryOver,
+) -> diesel::QueryResult<(db::Segment, db::Segment, bool)> {
+  let old: db::Segment = db::segments::table
+    .find(edit.id)
+    .select(db::Segment::as_select())
+    .for_update()
+    .first(con)
+    .await?;
+
+  let votes_reset = !carry_over.keeps_votes(
+    edit.category != old.category,
+    (old.start, old.end),
+    (edit.start, edit.end),
+  );
+
+  diesel::insert_into(db::segment_revisions::table)
+    .values(db::NewSegmentRevision {
+      segment: old.id,
+      category: old.category,
+      start: old.start,
+      end: old.end,
+      editor,
+      editor_ip,
+      votes_reset,
+      time: SystemTime::now(),
+    })
+    .execute(con)
+    .await?;
+
+  let segment: db::Segment = diesel::update(db::segments::table.find(old.id))
+    .set((
+      db::segments::category.eq(edit.category),
+      db::segments::start.eq(edit.start),
+      db::segments::end.eq(edit.end),
+      // the range is validated against the current duration, so it is no longer stale
+      db::segments::video_duration.eq(edit.video_duration),
+    ))
+    .returning(db::Segment::as_returning())
+    .get_result(con)
+    .await?;
+
+  if votes_reset {
+    diesel::delete(db::votes::table.filter(db::votes::segment.eq(old.id)))
+      .execute(con)
+      .await?;
+  }
+
+  Ok((old, segment, votes_reset))
+}
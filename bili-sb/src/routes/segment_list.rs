@@ -1,10 +1,35 @@
-use std::{mem::transmute, num::NonZeroU64};
+use std::{mem::transmute, num::NonZeroU64, time::SystemTime};
 
 use super::prelude::*;
 
+#[derive(Deserialize, Debug)]
+pub struct ListSegmentReq {
+  #[serde(flatten)]
+  pub target: ListSegmentTarget,
+  #[serde(flatten)]
+  pub filter: db::SegmentFilter,
+  #[serde(default)]
+  pub mode: ListMode,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListMode {
+  /// Every visible segment
+  All,
+  /// One segment per group of overlapping segments, see [crate::selection]
+  Best,
+}
+
+impl Default for ListMode {
+  fn default() -> Self {
+    Self::All
+  }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(untagged)]
-pub enum ListSegmentReq {
+pub enum ListSegmentTarget {
   /// aid or bvid, lookup related cids for video
   Abv {
     #[serde(flatten)]
@@ -26,14 +51,20 @@ pub async fn segment_list(
   state: AppState,
   body: Json<ListSegmentReq>,
 ) -> AppResult<Resp<ListSegmentData>> {
-  use ListSegmentReq as R;
+  use ListSegmentTarget as R;
 
-  let segments: Vec<db::SegmentWithVote> = match body.0 {
+  let ListSegmentReq {
+    target,
+    filter,
+    mode,
+  } = body.0;
+  let network = state.config.voting.network();
+  let mut segments: Vec<db::SegmentWithVote> = match target {
     R::Abv { abv } => {
       let mut db_con = state.db_con().await?;
       let aid = abv.as_i64();
 
-      db::segments_related_to_aid(&mut db_con, aid)
+      db::segments_related_to_aid(&mut db_con, aid, &filter, network)
         .await
         .with_context_into_app(|| format!("Failed to fetch segments for aid {aid}"))?
     },
@@ -41,7 +72,7 @@ pub async fn segment_list(
       let mut db_con = state.db_con().await?;
       let cid = cid.get() as i64;
 
-      db::segments_related_to_cid(&mut db_con, cid)
+      db::segments_related_to_cid(&mut db_con, cid, &filter, network)
         .await
         .with_context_into_app(|| format!("Failed to fetch segments for cid {cid}"))?
     },
@@ -60,7 +91,7 @@ pub async fn segment_list(
       let cids: Vec<i64> =
         unsafe { Vec::from_raw_parts(transmute(cids.as_mut_ptr()), cids.len(), cids.capacity()) };
 
-      db::segments_related_to_cids(&mut db_con, &cids)
+      db::segments_related_to_cids(&mut db_con, &cids, &filter, network)
         .await
         .with_context_into_app(|| {
           format!(
@@ -71,6 +102,12 @@ pub async fn segment_list(
     },
   };
 
+  metadata::record_views(&state, segments.iter().map(|segment| segment.cid));
+
+  if mode == ListMode::Best {
+    segments = selection::best_segments(segments, &state.config.voting, SystemTime::now());
+  }
+
   Ok(
     ListSegmentData {
       len: segments.len(),
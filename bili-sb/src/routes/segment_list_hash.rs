@@ -0,0 +1,65 @@
+use super::prelude::*;
+
+/// k-anonymity lookup, the server returns segments of every video matching the prefix,
+/// clients pick their own by `cid`
+#[derive(Deserialize, Debug)]
+pub struct ListSegmentByHashReq {
+  #[serde(flatten)]
+  pub target: HashTarget,
+  #[serde(flatten)]
+  pub filter: db::SegmentFilter,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum HashTarget {
+  /// prefix of SHA-256 of bvid, see [Abv::bv_hash]
+  BvidHash(String),
+  /// prefix of SHA-256 of cid in decimal
+  CidHash(String),
+}
+
+/// Parsed in handlers rather than by serde, so that invalid prefixes get the usual error response
+pub(crate) fn parse_hash_prefix(prefix: &str) -> AppResult<HashPrefix> {
+  prefix.parse().map_err(|error| {
+    app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "Invalid hash prefix `{}`, {}",
+      prefix,
+      error
+    )
+  })
+}
+
+pub async fn segment_list_by_hash(
+  state: AppState,
+  body: Json<ListSegmentByHashReq>,
+) -> AppResult<Resp<ListSegmentData>> {
+  let ListSegmentByHashReq { target, filter } = body.0;
+  let network = state.config.voting.network();
+  let mut db_con = state.db_con().await?;
+
+  let segments: Vec<db::SegmentWithVote> = match target {
+    HashTarget::BvidHash(prefix) => {
+      let prefix = parse_hash_prefix(&prefix)?;
+      db::segments_related_to_bvid_hash(&mut db_con, &prefix, &filter, network)
+        .await
+        .with_context_into_app(|| format!("Failed to fetch segments for bvid hash {prefix}"))?
+    },
+    HashTarget::CidHash(prefix) => {
+      let prefix = parse_hash_prefix(&prefix)?;
+      db::segments_related_to_cid_hash(&mut db_con, &prefix, &filter, network)
+        .await
+        .with_context_into_app(|| format!("Failed to fetch segments for cid hash {prefix}"))?
+    },
+  };
+
+  Ok(
+    ListSegmentData {
+      len: segments.len(),
+      segments,
+    }
+    .into(),
+  )
+}
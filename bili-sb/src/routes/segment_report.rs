@@ -0,0 +1,100 @@
+use std::time::SystemTime;
+
+use diesel::{OptionalExtension, SelectableHelper};
+
+use super::prelude::*;
+
+#[derive(Deserialize, Debug)]
+pub struct ReportSegmentReq {
+  pub id: Uuid,
+  /// Private secret of the reporter
+  pub reporter: Uuid,
+  pub reason: db::ReportReason,
+  #[serde(default)]
+  pub comment: String,
+}
+
+impl ReportSegmentReq {
+  pub const MAX_COMMENT_LEN: usize = 500;
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReportSegmentData {
+  pub id: Uuid,
+  /// `false` if the reporter already has an open report on the segment
+  pub reported: bool,
+}
+
+/// Reports a segment to moderators, open reports are listed in the moderation queue
+pub async fn segment_report(
+  state: AppState,
+  ip: SecureClientIp,
+  ip_shadow_banned: Option<Extension<IpShadowBanned>>,
+  body: Json<ReportSegmentReq>,
+) -> AppResult<Resp<ReportSegmentData>> {
+  if body.comment.chars().count() > ReportSegmentReq::MAX_COMMENT_LEN {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "Comment is longer than {} characters",
+      ReportSegmentReq::MAX_COMMENT_LEN
+    ));
+  }
+
+  let mut db_con = state.db_con().await?;
+  let mut user = authenticate(&mut db_con, &body.reporter).await?;
+  shadow_ban_by_ip(&mut db_con, &mut user, ip_shadow_banned).await?;
+
+  let segment: Option<db::Segment> = db::segments::table
+    .find(body.id)
+    .select(db::Segment::as_select())
+    .first(&mut db_con)
+    .await
+    .optional()
+    .with_context_into_app(|| format!("Failed to fetch segment, uuid = {}", body.id))?;
+  let Some(segment) = segment.filter(|segment| segment.deleted_at.is_none() && !segment.hidden)
+  else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such segment, uuid = {}",
+      body.id
+    ));
+  };
+
+  if segment.submitter == user.id {
+    return Err(app_err_custom!(
+      StatusCode::FORBIDDEN,
+      RespCode::PERMISSION_DENIED,
+      "Cannot report own segment, uuid = {}",
+      body.id
+    ));
+  }
+
+  let report = db::NewReport {
+    segment: segment.id,
+    reporter: user.id,
+    reporter_ip: ip.0.into(),
+    reason: body.reason,
+    comment: body.comment.clone(),
+    time: SystemTime::now(),
+  };
+  let inserted = diesel::insert_into(db::reports::table)
+    .values(&report)
+    .on_conflict_do_nothing()
+    .execute(&mut db_con)
+    .await
+    .with_context_into_app(|| format!("Failed to insert report: {report:?}"))?;
+
+  debug!(
+    "User {} reported segment {}, reason = {:?}",
+    user.id, segment.id, body.reason
+  );
+  Ok(
+    ReportSegmentData {
+      id: segment.id,
+      reported: inserted != 0,
+    }
+    .into(),
+  )
+}
@@ -0,0 +1,31 @@
+use super::prelude::*;
+
+#[derive(Deserialize, Debug)]
+pub struct ListRevisionsReq {
+  pub id: Uuid,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ListRevisionsData {
+  pub len: usize,
+  /// Newest first
+  pub revisions: Vec<db::SegmentRevision>,
+}
+
+pub async fn segment_revisions(
+  state: AppState,
+  body: Json<ListRevisionsReq>,
+) -> AppResult<Resp<ListRevisionsData>> {
+  let mut db_con = state.db_con().await?;
+  let revisions = db::revisions_of_segment(&mut db_con, body.id)
+    .await
+    .with_context_into_app(|| format!("Failed to fetch revisions of segment {}", body.id))?;
+
+  Ok(
+    ListRevisionsData {
+      len: revisions.len(),
+      revisions,
+    }
+    .into(),
+  )
+}
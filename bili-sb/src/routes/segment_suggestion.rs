@@ -0,0 +1,279 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use diesel_async::AsyncPgConnection;
+use ipnet::IpNet;
+
+use super::{
+  prelude::*,
+  segment_create::{check_duration, segment_range},
+  segment_edit::{apply_edit, SegmentEdit},
+};
+
+/// Times are rounded to 0.1 seconds, so that close suggestions support each other
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SuggestionReq {
+  Category {
+    category: db::SegmentCategory,
+  },
+  /// Not accepted for [db::ActionType::Full]
+  Start {
+    value: f32,
+  },
+  /// Only accepted for [db::ActionType::Skip] and [db::ActionType::Mute]
+  End {
+    value: f32,
+  },
+}
+
+#[derive(Serialize)]
+pub struct SuggestionResp {
+  /// Weighted support of the suggested change
+  pub support: i64,
+  /// Whether the change has been applied to the segment
+  pub applied: bool,
+}
+
+/// A proposed change, times in tenths of a second
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Change {
+  Category(db::SegmentCategory),
+  Start(i32),
+  End(i32),
+}
+
+impl Change {
+  fn of(suggestion: &db::Suggestion) -> Option<Self> {
+    match (suggestion.kind, suggestion.category, suggestion.value) {
+      (db::SuggestionKind::Category, Some(category), _) => Some(Self::Category(category)),
+      (db::SuggestionKind::Start, _, Some(value)) => Some(Self::Start(tenths(value))),
+      (db::SuggestionKind::End, _, Some(value)) => Some(Self::End(tenths(value))),
+      _ => None,
+    }
+  }
+
+  fn kind(self) -> db::SuggestionKind {
+    match self {
+      Self::Category(_) => db::SuggestionKind::Category,
+      Self::Start(_) => db::SuggestionKind::Start,
+      Self::End(_) => db::SuggestionKind::End,
+    }
+  }
+}
+
+fn tenths(value: f32) -> i32 {
+  (value * 10.0).round() as i32
+}
+
+/// Validates a suggestion against the current segment
+pub(super) fn suggestion_of(
+  segment: &db::Segment,
+  video_duration: f32,
+  suggestion: SuggestionReq,
+  user: &db::User,
+  voter_ip: IpNet,
+) -> AppResult<db::Suggestion> {
+  let (kind, category, value) = match suggestion {
+    SuggestionReq::Category { category } => (db::SuggestionKind::Category, Some(category), None),
+    SuggestionReq::Start { value } => (
+      db::SuggestionKind::Start,
+      None,
+      Some(tenths(value) as f32 / 10.0),
+    ),
+    SuggestionReq::End { value } => (
+      db::SuggestionKind::End,
+      None,
+      Some(tenths(value) as f32 / 10.0),
+    ),
+  };
+
+  let (start, end) = match segment.action_type {
+    db::ActionType::Full => (None, None),
+    db::ActionType::Poi => (Some(segment.start), None),
+    db::ActionType::Skip | db::ActionType::Mute => (Some(segment.start), Some(segment.end)),
+  };
+  let (start, end) = match kind {
+    db::SuggestionKind::Category => (start, end),
+    db::SuggestionKind::Start => (value, end),
+    db::SuggestionKind::End => (start, value),
+  };
+  let (_, end) = segment_range(
+    category.unwrap_or(segment.category),
+    segment.action_type,
+    start,
+    end,
+  )?;
+  check_duration(end, video_duration)?;
+
+  Ok(db::Suggestion {
+    segment: segment.id,
+    voter: user.id,
+    kind,
+    category,
+    value,
+    voter_ip,
+    time: SystemTime::now(),
+  })
+}
+
+/// Stores a validated suggestion, replacing the voter's previous one of the same kind, then
+/// applies suggestions on the segment, see [apply_suggestions]
+///
+/// Runs in the transaction of the vote, so that the vote and the suggestion are kept together
+pub(super) async fn save_suggestion(
+  con: &mut AsyncPgConnection,
+  config: &Config,
+  segment: &db::Segment,
+  aid: i64,
+  video_duration: f32,
+  suggestion: &db::Suggestion,
+) -> diesel::QueryResult<SuggestionResp> {
+  diesel::insert_into(db::suggestions::table)
+    .values(suggestion)
+    .on_conflict((
+      db::suggestions::segment,
+      db::suggestions::voter,
+      db::suggestions::kind,
+    ))
+    .do_update()
+    .set(suggestion)
+    .execute(con)
+    .await?;
+
+  apply_suggestions(con, config, segment, aid, video_duration, suggestion).await
+}
+
+/// Applies the best supported change of each kind, once its support reaches
+/// [crate::config::SuggestionConfig::threshold]
+///
+/// Changes which are tied, no longer valid together, or in a locked scope are not applied
+async fn apply_suggestions(
+  con: &mut AsyncPgConnection,
+  config: &Config,
+  segment: &db::Segment,
+  aid: i64,
+  video_duration: f32,
+  suggested: &db::Suggestion,
+) -> diesel::QueryResult<SuggestionResp> {
+  let suggestions = db::suggestions_with_roles(con, segment.id).await?;
+
+  // suggestions of the same change from one network count once, with the highest weight
+  let network = config.voting.network();
+  let mut weights: HashMap<(Change, IpNet), i64> = HashMap::with_capacity(suggestions.len());
+  for (suggestion, role, trust) in suggestions {
+    let Some(change) = Change::of(&suggestion) else {
+      continue;
+    };
+    let weight = weights
+      .entry((change, network.network_of(suggestion.voter_ip)))
+      .or_default();
+    *weight = config.voting.weight(role, trust).max(*weight);
+  }
+  let mut support: HashMap<Change, i64> = HashMap::new();
+  for ((change, _), weight) in weights {
+    *support.entry(change).or_default() += weight;
+  }
+
+  let mut resp = SuggestionResp {
+    support: Change::of(suggested)
+      .and_then(|change| support.get(&change).copied())
+      .unwrap_or_default(),
+    applied: false,
+  };
+
+  let threshold = config.suggestion.threshold;
+  let accepted: Vec<(Change, i64)> = [
+    db::SuggestionKind::Category,
+    db::SuggestionKind::Start,
+    db::SuggestionKind::End,
+  ]
+  .into_iter()
+  .filter_map(|kind| {
+    let mut candidates: Vec<(Change, i64)> = support
+      .iter()
+      .filter(|(change, support)| change.kind() == kind && **support >= threshold)
+      .map(|(change, support)| (*change, *support))
+      .collect();
+    candidates.sort_unstable_by_key(|(_, support)| -support);
+    match candidates.as_slice() {
+      [first, second, ..] if first.1 == second.1 => None,
+      [first, ..] => Some(*first),
+      [] => None,
+    }
+  })
+  .collect();
+  if accepted.is_empty() {
+    return Ok(resp);
+  }
+
+  let mut edit = SegmentEdit {
+    id: segment.id,
+    category: segment.category,
+    start: segment.start,
+    end: segment.end,
+    video_duration,
+  };
+  for (change, _) in &accepted {
+    match *change {
+      Change::Category(category) => edit.category = category,
+      Change::Start(start) => edit.start = start as f32 / 10.0,
+      Change::End(end) => edit.end = end as f32 / 10.0,
+    }
+  }
+  let (start, end) = match segment.action_type {
+    db::ActionType::Full => (None, None),
+    db::ActionType::Poi => (Some(edit.start), None),
+    db::ActionType::Skip | db::ActionType::Mute => (Some(edit.start), Some(edit.end)),
+  };
+  let valid = segment_range(edit.category, segment.action_type, start, end)
+    .and_then(|(_, end)| check_duration(end, video_duration));
+  if let Err(err) = valid {
+    debug!(
+      "Suggestions on segment {} not applied, {:?}",
+      segment.id, err.0
+    );
+    return Ok(resp);
+  }
+
+  for category in [segment.category, edit.category] {
+    let lock = db::lock_of(con, aid, segment.cid, category).await?;
+    if let Some(lock) = lock {
+      debug!(
+        "Suggestions on segment {} not applied, locked by {:?}",
+        segment.id, lock
+      );
+      return Ok(resp);
+    }
+  }
+
+  let carry_over = config.segment_edit.votes;
+  let kinds: Vec<db::SuggestionKind> = accepted.iter().map(|(change, _)| change.kind()).collect();
+  let detail = accepted
+    .iter()
+    .map(|(change, support)| format!("{change:?}, support = {support}"))
+    .collect::<Vec<_>>()
+    .join("; ");
+  // the voter completing the suggestion is recorded as editor
+  let (old, _, votes_reset) =
+    apply_edit(con, &edit, suggested.voter, suggested.voter_ip, carry_over).await?;
+
+  diesel::delete(
+    db::suggestions::table
+      .filter(db::suggestions::segment.eq(old.id))
+      .filter(db::suggestions::kind.eq_any(kinds)),
+  )
+  .execute(con)
+  .await?;
+
+  let entry = db::NewAuditLog::new(None, db::AuditAction::SegmentSuggestion, old.id).detail(detail);
+  db::audit(con, &[entry]).await?;
+
+  debug!(
+    "Applied suggestions on segment {}, votes reset = {}",
+    segment.id, votes_reset
+  );
+  resp.applied = accepted
+    .iter()
+    .any(|(change, _)| Some(*change) == Change::of(suggested));
+  Ok(resp)
+}
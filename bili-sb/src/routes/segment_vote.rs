@@ -1,92 +1,227 @@
 use std::time::SystemTime;
 
-use super::prelude::*;
+use diesel::{OptionalExtension, SelectableHelper};
+use ipnet::IpNet;
+
+use super::{
+  prelude::*,
+  segment_suggestion::{save_suggestion, suggestion_of},
+};
 
 #[derive(Deserialize, Debug)]
 pub struct SegmentVoteReq {
   pub id: Uuid,
+  /// Private secret of the voter
   pub voter: Uuid,
   #[serde(default)]
-  pub r#type: db::VoteType,
+  pub r#type: VoteReqType,
+  /// Proposed change along with the vote, see [crate::config::SuggestionConfig]
+  #[serde(default)]
+  pub suggestion: Option<SuggestionReq>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteReqType {
+  Up,
+  Down,
+  /// Retracts the previous vote, if any
+  #[serde(alias = "neutral")]
+  Undo,
+}
+
+impl Default for VoteReqType {
+  fn default() -> Self {
+    Self::Up
+  }
 }
 
 #[derive(Serialize)]
 pub struct SegmentVoteResp {
   pub up: i64,
   pub down: i64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub suggestion: Option<SuggestionResp>,
 }
 
 pub async fn segment_vote(
   state: AppState,
   ip: SecureClientIp,
+  ip_shadow_banned: Option<Extension<IpShadowBanned>>,
   body: Json<SegmentVoteReq>,
 ) -> AppResult<Resp<SegmentVoteResp>> {
   let mut db_con: PooledPgCon = state.db_con().await?;
-  let segment_exist = db::segments::table
+  let mut user = authenticate(&mut db_con, &body.voter).await?;
+  shadow_ban_by_ip(&mut db_con, &mut user, ip_shadow_banned).await?;
+
+  let segment: Option<(db::Segment, i64, f32)> = db::segments::table
+    .inner_join(db::video_parts::table)
     .filter(db::segments::id.eq(body.id))
-    .count()
-    .get_result::<i64>(&mut db_con)
+    .select((
+      db::Segment::as_select(),
+      db::video_parts::aid,
+      db::video_parts::duration,
+    ))
+    .first(&mut db_con)
     .await
-    .with_context_into_app(|| format!("No such segment, uuid = {}", body.id))?
-    == 1;
+    .optional()
+    .with_context_into_app(|| format!("Failed to fetch segment, uuid = {}", body.id))?;
 
-  if !segment_exist {
+  // hidden segments are only visible to moderators, deleted ones to nobody
+  let (segment, aid, video_duration) = match segment {
+    Some((segment, aid, video_duration))
+      if segment.deleted_at.is_none()
+        && (!segment.hidden || user.role >= db::UserRole::Moderator) =>
+    {
+      (segment, aid, video_duration)
+    },
+    _ => {
+      return Err(app_err_custom!(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        RespCode::INVALID_PARAMS,
+        "No such segment, uuid = {}",
+        body.id
+      ))
+    },
+  };
+  let submitter = segment.submitter;
+
+  let vote_type = match body.r#type {
+    VoteReqType::Up => Some(db::VoteType::Up),
+    VoteReqType::Down => Some(db::VoteType::Down),
+    VoteReqType::Undo => None,
+  };
+
+  // retracting is always allowed, e.g. votes cast before self votes were rejected
+  if submitter == user.id && vote_type.is_some() {
     return Err(app_err_custom!(
-      StatusCode::UNPROCESSABLE_ENTITY,
-      RespCode::INVALID_PARAMS,
-      "No such segment, uuid = {}",
+      StatusCode::FORBIDDEN,
+      RespCode::PERMISSION_DENIED,
+      "Cannot vote on own segment, uuid = {}",
       body.id
     ));
   }
 
-  let user_exist = db::users::table
-    .filter(db::users::id.eq(body.voter))
-    .count()
-    .get_result::<i64>(&mut db_con)
-    .await
-    .with_context_into_app(|| format!("No such segment, uuid = {}", body.id))?
-    == 1;
-
-  if !user_exist {
-    return Err(app_err_custom!(
-      StatusCode::UNPROCESSABLE_ENTITY,
-      RespCode::INVALID_PARAMS,
-      "No such user, uuid = {}",
-      body.voter
-    ));
+  if vote_type == Some(db::VoteType::Down) && user.role < db::UserRole::Vip {
+    let lock = db::lock_of(&mut db_con, aid, segment.cid, segment.category)
+      .await
+      .with_context_into_app(|| format!("Failed to fetch lock of segment {}", body.id))?;
+    if lock.is_some() {
+      return Err(app_err_custom!(
+        StatusCode::FORBIDDEN,
+        RespCode::SEGMENT_LOCKED,
+        "Segment is locked, only VIPs can downvote it, uuid = {}",
+        body.id
+      ));
+    }
   }
 
-  let vote = db::Vote {
-    segment: body.id,
-    type_: body.r#type,
-    voter: body.voter,
-    voter_ip: ip.0.into(),
-    time: SystemTime::now(),
+  let suggestion = match (vote_type, body.suggestion) {
+    (_, None) => None,
+    (None, Some(_)) => {
+      return Err(app_err_custom!(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        RespCode::INVALID_PARAMS,
+        "Cannot suggest a change while retracting the vote, uuid = {}",
+        body.id
+      ))
+    },
+    (Some(_), Some(suggestion)) => Some(suggestion_of(
+      &segment,
+      video_duration,
+      suggestion,
+      &user,
+      ip.0.into(),
+    )?),
   };
 
-  diesel::insert_into(db::votes::table)
-    .values(&vote)
-    .on_conflict((db::votes::segment, db::votes::voter))
-    .do_update()
-    .set(&vote)
-    .execute(&mut db_con)
-    .await
-    .with_context_into_app(|| format!("Failed to upsert votes, request: {:?}", &body.0))?;
+  let segment_id = body.id;
+  let voter = user.id;
+  let voter_ip: IpNet = ip.0.into();
+  let config = state.config.as_ref();
+  let segment_ref = &segment;
+  let suggestion_ref = suggestion.as_ref();
+  let mut suggestion = db_con
+    .build_transaction()
+    .run::<_, diesel::result::Error, _>(|con| {
+      async move {
+        if let Some(vote_type) = vote_type {
+          let vote = db::Vote {
+            segment: segment_id,
+            type_: vote_type,
+            voter,
+            voter_ip,
+            time: SystemTime::now(),
+          };
+          diesel::insert_into(db::votes::table)
+            .values(&vote)
+            .on_conflict((db::votes::segment, db::votes::voter))
+            .do_update()
+            .set(&vote)
+            .execute(con)
+            .await?;
+        } else {
+          diesel::delete(db::votes::table.find((segment_id, voter)))
+            .execute(con)
+            .await?;
+          diesel::delete(
+            db::suggestions::table
+              .filter(db::suggestions::segment.eq(segment_id))
+              .filter(db::suggestions::voter.eq(voter)),
+          )
+          .execute(con)
+          .await?;
+        }
 
-  let up_votes: i64 = db::count_votes(&mut db_con, body.id, db::VoteType::Up)
-    .await
-    .with_context_into_app(|| format!("Failed to get up vote count for request: {:?}", &body.0))?;
+        let suggestion = match suggestion_ref {
+          Some(suggestion) => {
+            Some(save_suggestion(con, config, segment_ref, aid, video_duration, suggestion).await?)
+          },
+          None => None,
+        };
 
-  let down_votes = db::count_votes(&mut db_con, body.id, db::VoteType::Down)
+        refresh_visibility(con, segment_id, &config.voting).await?;
+        Ok(suggestion)
+      }
+      .scope_boxed()
+    })
     .await
     .with_context_into_app(|| {
-      format!("Failed to get down vote count for request: {:?}", &body.0)
+      format!(
+        "Failed to vote {:?} on segment {}, voter = {}",
+        vote_type, segment_id, voter
+      )
     })?;
 
+  // suggestions of shadow banned users are not counted, pretend they are
+  if let Some(resp) = &mut suggestion {
+    if user.shadow_banned && !resp.applied {
+      resp.support += 1;
+    }
+  }
+
+  let mut up_votes: i64 = db::count_votes(&mut db_con, body.id, db::VoteType::Up)
+    .await
+    .with_context_into_app(|| format!("Failed to get up vote count of segment {}", body.id))?;
+
+  let mut down_votes = db::count_votes(&mut db_con, body.id, db::VoteType::Down)
+    .await
+    .with_context_into_app(|| format!("Failed to get down vote count of segment {}", body.id))?;
+
+  // votes of shadow banned users are not counted, pretend they are
+  if user.shadow_banned {
+    match vote_type {
+      Some(db::VoteType::Up) => up_votes += 1,
+      Some(db::VoteType::Down) => down_votes += 1,
+      None => {},
+    }
+  }
+
   Ok(
     SegmentVoteResp {
       up: up_votes,
       down: down_votes,
+      suggestion,
     }
     .into(),
   )
@@ -2,12 +2,16 @@ use super::prelude::*;
 
 #[derive(Serialize)]
 pub struct CreateUserData {
+  /// Private secret for writes, as `submitter` or `voter`, keep it safe
   pub uuid: Uuid,
+  /// Public id derived from the secret, see [db::User::public_id]
+  pub id: Uuid,
 }
 
 pub async fn user_create(state: AppState, ip: SecureClientIp) -> AppResult<Resp<CreateUserData>> {
   let mut con = state.db_con().await?;
-  let user = db::User::new(ip.0.into());
+  let secret = Uuid::new_v4();
+  let user = db::User::new(&secret, ip.0.into());
   let result = diesel::insert_into(db::users::table)
     .values(&user)
     .execute(&mut con)
@@ -18,5 +22,11 @@ pub async fn user_create(state: AppState, ip: SecureClientIp) -> AppResult<Resp<
     return Err(app_err!(RespCode::DATABASE_ERROR, "Database insert failed"));
   }
 
-  Ok(CreateUserData { uuid: user.id }.into())
+  Ok(
+    CreateUserData {
+      uuid: secret,
+      id: user.id,
+    }
+    .into(),
+  )
 }
@@ -0,0 +1,63 @@
+use std::time::SystemTime;
+
+use diesel::{OptionalExtension, SelectableHelper};
+
+use super::prelude::*;
+
+#[derive(Deserialize, Debug)]
+pub struct UserInfoReq {
+  /// Public id
+  pub id: Uuid,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserInfoData {
+  pub id: Uuid,
+  pub role: db::UserRole,
+  /// See [crate::trust]
+  pub trust: f32,
+  #[serde(with = "humantime_serde")]
+  pub register_time: SystemTime,
+  /// Segments not deleted
+  pub segment_count: i64,
+}
+
+pub async fn user_info(state: AppState, body: Json<UserInfoReq>) -> AppResult<Resp<UserInfoData>> {
+  let mut db_con = state.db_con().await?;
+  let id = body.id;
+
+  let user: Option<db::User> = db::users::table
+    .find(id)
+    .select(db::User::as_select())
+    .first(&mut db_con)
+    .await
+    .optional()
+    .with_context_into_app(|| format!("Failed to fetch user, id = {id}"))?;
+  let Some(user) = user else {
+    return Err(app_err_custom!(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      RespCode::INVALID_PARAMS,
+      "No such user, id = {}",
+      id
+    ));
+  };
+
+  let segment_count = db::segments::table
+    .filter(db::segments::submitter.eq(id))
+    .filter(db::segments::deleted_at.is_null())
+    .count()
+    .get_result::<i64>(&mut db_con)
+    .await
+    .with_context_into_app(|| format!("Failed to count segments of user {id}"))?;
+
+  Ok(
+    UserInfoData {
+      id,
+      role: user.role,
+      trust: user.trust,
+      register_time: user.register_time,
+      segment_count,
+    }
+    .into(),
+  )
+}
@@ -0,0 +1,155 @@
+//! Picks the best segment out of each group of overlapping segments, so that every client
+//! shows the same result for `mode=best`
+
+use std::time::{Duration, SystemTime};
+
+use crate::{
+  config::VotingConfig,
+  db::{ActionType, SegmentWithVote, UserRole},
+};
+
+/// Age at which [age_bonus] is saturated
+const MATURE_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Returns one segment per group of overlapping segments, ordered by `cid` and `start`
+///
+/// Low confidence segments are left out until they are voted up, see [crate::trust], and so are
+/// segments which [VotingConfig::should_hide] by their effective votes.
+///
+/// Segments are grouped per `cid` and [ActionType]. Skip and mute ranges are grouped when they
+/// overlap, full video labels and points of interest are grouped as a whole.
+pub fn best_segments(
+  mut segments: Vec<SegmentWithVote>,
+  config: &VotingConfig,
+  now: SystemTime,
+) -> Vec<SegmentWithVote> {
+  segments.retain(|segment| {
+    let count = segment.effective_up_vote.unwrap_or(0) + segment.effective_down_vote.unwrap_or(0);
+    !segment.low_confidence && !config.should_hide(votes(segment), count as usize)
+  });
+  segments.sort_by(|a, b| {
+    (a.cid, action_order(a.action_type))
+      .cmp(&(b.cid, action_order(b.action_type)))
+      .then(a.start.total_cmp(&b.start))
+  });
+
+  let mut best: Vec<SegmentWithVote> = Vec::new();
+  let mut group_end = f32::NEG_INFINITY;
+  for segment in segments {
+    let same_group = best.last().is_some_and(|last| {
+      last.cid == segment.cid
+        && last.action_type == segment.action_type
+        && match segment.action_type {
+          ActionType::Skip | ActionType::Mute => segment.start < group_end,
+          ActionType::Full | ActionType::Poi => true,
+        }
+    });
+
+    if !same_group {
+      group_end = segment.end;
+      best.push(segment);
+      continue;
+    }
+
+    group_end = group_end.max(segment.end);
+    let last = best.last_mut().unwrap(); // same_group implies non-empty
+    let (new_score, last_score) = (score(&segment, now), score(last, now));
+    // older segment wins on a tie, it has been around for longer
+    if new_score > last_score || (new_score == last_score && segment.time < last.time) {
+      *last = segment;
+    }
+  }
+
+  best
+}
+
+/// Higher is better, votes weigh the most, then the submitter's trust, then age
+pub fn score(segment: &SegmentWithVote, now: SystemTime) -> f32 {
+  votes(segment) as f32
+    + trust(segment.submitter_role, segment.submitter_trust)
+    + age_bonus(segment.time, now)
+    - if segment.stale { 1.0 } else { 0.0 }
+}
+
+/// Effective votes, so that stacking votes from one network does not help
+fn votes(segment: &SegmentWithVote) -> i64 {
+  segment.effective_up_vote.unwrap_or(0) - segment.effective_down_vote.unwrap_or(0)
+}
+
+/// Up to 3 from the submitter's role, plus up to 1.5 either way from trust
+fn trust(role: UserRole, trust: f32) -> f32 {
+  let role = match role {
+    UserRole::Normal => 0.0,
+    UserRole::Vip => 2.0,
+    UserRole::Moderator | UserRole::Admin => 3.0,
+  };
+  role + trust.clamp(-3.0, 3.0) * 0.5
+}
+
+/// Up to 0.5 for segments that survived [MATURE_AGE] without being voted down
+fn age_bonus(time: SystemTime, now: SystemTime) -> f32 {
+  let age = now.duration_since(time).unwrap_or_default();
+  0.5 * (age.as_secs_f32() / MATURE_AGE.as_secs_f32()).min(1.0)
+}
+
+fn action_order(action_type: ActionType) -> u8 {
+  match action_type {
+    ActionType::Skip => 0,
+    ActionType::Mute => 1,
+    ActionType::Full => 2,
+    ActionType::Poi => 3,
+  }
+}
+
+#[test]
+fn best_segments_test() {
+  use crate::db::SegmentCategory;
+  use uuid::Uuid;
+
+  let now = SystemTime::now();
+  let segment = |cid: i64, action_type, start: f32, end: f32, up: i64, down: i64| SegmentWithVote {
+    id: Uuid::new_v4(),
+    cid,
+    category: SegmentCategory::Sponsor,
+    action_type,
+    start,
+    end,
+    time: now,
+    up_vote: Some(up),
+    down_vote: Some(down),
+    effective_up_vote: Some(up),
+    effective_down_vote: Some(down),
+    stale: false,
+    low_confidence: false,
+    submitter_role: UserRole::Normal,
+    submitter_trust: 0.0,
+  };
+
+  let a = segment(1, ActionType::Skip, 0.0, 10.0, 1, 0);
+  let b = segment(1, ActionType::Skip, 5.0, 12.0, 3, 0);
+  let c = segment(1, ActionType::Skip, 20.0, 30.0, 0, 0);
+  let d = segment(1, ActionType::Skip, 40.0, 50.0, 0, 5);
+  let e = segment(1, ActionType::Mute, 0.0, 10.0, 0, 0);
+  let f = segment(2, ActionType::Skip, 0.0, 10.0, 0, 0);
+  let mut g = segment(2, ActionType::Skip, 1.0, 9.0, 0, 0);
+  g.submitter_role = UserRole::Vip;
+  let mut h = segment(3, ActionType::Skip, 0.0, 10.0, 0, 0);
+  h.low_confidence = true;
+
+  let best = best_segments(
+    vec![
+      a.clone(),
+      b.clone(),
+      c.clone(),
+      d.clone(),
+      e.clone(),
+      f.clone(),
+      g.clone(),
+      h.clone(),
+    ],
+    &VotingConfig::default(),
+    now,
+  );
+  let ids: Vec<Uuid> = best.iter().map(|segment| segment.id).collect();
+  assert_eq!(ids, vec![b.id, c.id, e.id, g.id]);
+}
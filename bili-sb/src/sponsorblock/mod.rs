@@ -0,0 +1,157 @@
+//! SponsorBlock compatible API, so that existing SponsorBlock clients can be reused
+//!
+//! See: https://wiki.sponsor.ajay.app/w/API_Docs
+
+use std::{collections::HashMap, num::NonZeroU64};
+
+use axum::{
+  routing::{get, post},
+  Router,
+};
+
+use crate::routes::prelude::*;
+
+mod skip_segments;
+mod user_info;
+mod vote;
+
+pub use skip_segments::*;
+pub use vote::*;
+
+/// Routes to be nested under `/api`
+pub fn router() -> Router<Arc<App>> {
+  Router::new()
+    .route("/skipSegments", get(skip_segments))
+    .route("/skipSegments/:prefix", get(skip_segments_by_hash))
+    .route("/voteOnSponsorTime", post(vote_on_sponsor_time))
+    .route("/userInfo", get(user_info::user_info))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentQuery {
+  /// bvid or aid, required by `/api/skipSegments`
+  #[serde(rename = "videoID")]
+  pub video_id: Option<String>,
+  pub cid: Option<NonZeroU64>,
+  pub category: Option<db::SegmentCategory>,
+  /// JSON array of categories
+  pub categories: Option<String>,
+  pub action_type: Option<db::ActionType>,
+  /// JSON array of action types
+  pub action_types: Option<String>,
+}
+
+impl SegmentQuery {
+  /// Defaults to `sponsor` category with `skip` and `mute` action types, as SponsorBlock does
+  pub fn filter(&self) -> AppResult<db::SegmentFilter> {
+    let mut categories: Vec<db::SegmentCategory> =
+      parse_json_param("categories", &self.categories)?;
+    categories.extend(self.category);
+    if categories.is_empty() {
+      categories.push(db::SegmentCategory::Sponsor);
+    }
+
+    let mut action_types: Vec<db::ActionType> =
+      parse_json_param("actionTypes", &self.action_types)?;
+    action_types.extend(self.action_type);
+    if action_types.is_empty() {
+      action_types.extend([db::ActionType::Skip, db::ActionType::Mute]);
+    }
+
+    Ok(db::SegmentFilter {
+      categories,
+      action_types,
+    })
+  }
+}
+
+fn parse_json_param<T>(name: &str, param: &Option<String>) -> AppResult<Vec<T>>
+where
+  T: serde::de::DeserializeOwned,
+{
+  let Some(json) = param else {
+    return Ok(Vec::new());
+  };
+  serde_json::from_str(json).map_err(|error| {
+    app_err_custom!(
+      StatusCode::BAD_REQUEST,
+      RespCode::INVALID_PARAMS,
+      "Invalid `{}` param, {}",
+      name,
+      error
+    )
+  })
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SbSegment {
+  pub cid: i64,
+  pub category: db::SegmentCategory,
+  pub action_type: db::ActionType,
+  pub segment: [f32; 2],
+  #[serde(rename = "UUID")]
+  pub uuid: Uuid,
+  pub video_duration: f32,
+  pub locked: u8,
+  pub votes: i64,
+  pub description: &'static str,
+}
+
+/// Attaches part durations and locks to segments, returns `(aid, segment)` pairs
+async fn to_sb_segments(
+  con: &mut PooledPgCon<'_>,
+  segments: Vec<db::SegmentWithVote>,
+) -> AppResult<Vec<(i64, SbSegment)>> {
+  let mut cids: Vec<i64> = segments.iter().map(|segment| segment.cid).collect();
+  cids.sort_unstable();
+  cids.dedup();
+
+  let parts: HashMap<i64, db::VideoPart> = db::parts_of_cids(con, &cids)
+    .await
+    .context_into_app("Failed to fetch video parts")?
+    .into_iter()
+    .map(|part| (part.cid, part))
+    .collect();
+
+  let mut aids: Vec<i64> = parts.values().map(|part| part.aid).collect();
+  aids.sort_unstable();
+  aids.dedup();
+
+  let mut locks: HashMap<i64, Vec<db::Lock>> = HashMap::new();
+  for lock in db::locks_of_aids(con, &aids)
+    .await
+    .context_into_app("Failed to fetch locks")?
+  {
+    locks.entry(lock.aid).or_default().push(lock);
+  }
+
+  Ok(
+    segments
+      .into_iter()
+      // clients skip without asking, only serve segments of trusted users or voted up
+      .filter(|segment| !segment.low_confidence)
+      .filter_map(|segment| {
+        let part = parts.get(&segment.cid)?;
+        let locked = locks.get(&part.aid).is_some_and(|locks| {
+          locks
+            .iter()
+            .any(|lock| lock.covers(segment.cid, segment.category))
+        });
+        let sb_segment = SbSegment {
+          cid: segment.cid,
+          category: segment.category,
+          action_type: segment.action_type,
+          segment: [segment.start, segment.end],
+          uuid: segment.id,
+          video_duration: part.duration,
+          locked: locked.into(),
+          votes: segment.effective_up_vote.unwrap_or(0) - segment.effective_down_vote.unwrap_or(0),
+          description: "",
+        };
+        Some((part.aid, sb_segment))
+      })
+      .collect(),
+  )
+}
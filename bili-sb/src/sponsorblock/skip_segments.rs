@@ -0,0 +1,98 @@
+use axum::extract::{Path, Query};
+
+use super::*;
+
+/// `GET /api/skipSegments?videoID=BV1...&cid=...`
+pub async fn skip_segments(
+  state: AppState,
+  Query(query): Query<SegmentQuery>,
+) -> AppResult<Response> {
+  let Some(video_id) = &query.video_id else {
+    return Err(app_err_custom!(
+      StatusCode::BAD_REQUEST,
+      RespCode::INVALID_PARAMS,
+      "`videoID` is required"
+    ));
+  };
+  let abv: Abv = video_id.parse().map_err(|error| {
+    app_err_custom!(
+      StatusCode::BAD_REQUEST,
+      RespCode::INVALID_PARAMS,
+      "Invalid `videoID`, {:?}",
+      error
+    )
+  })?;
+  let filter = query.filter()?;
+  let network = state.config.voting.network();
+
+  let mut db_con = state.db_con().await?;
+  let aid = abv.as_i64();
+  let mut segments = db::segments_related_to_aid(&mut db_con, aid, &filter, network)
+    .await
+    .with_context_into_app(|| format!("Failed to fetch segments for aid {aid}"))?;
+  if let Some(cid) = query.cid {
+    segments.retain(|segment| segment.cid == cid.get() as i64);
+  }
+  metadata::record_views(&state, segments.iter().map(|segment| segment.cid));
+
+  let segments: Vec<SbSegment> = to_sb_segments(&mut db_con, segments)
+    .await?
+    .into_iter()
+    .map(|(_, segment)| segment)
+    .collect();
+
+  if segments.is_empty() {
+    return Ok((StatusCode::NOT_FOUND, Json(segments)).into_response());
+  }
+  Ok(Json(segments).into_response())
+}
+
+#[derive(Serialize, Debug)]
+pub struct SbVideo {
+  #[serde(rename = "videoID")]
+  pub video_id: String,
+  pub hash: String,
+  pub segments: Vec<SbSegment>,
+}
+
+/// `GET /api/skipSegments/:sha256HashPrefix`
+///
+/// The prefix is the leading hex characters of SHA-256 of bvid, see [Abv::bv_hash]
+pub async fn skip_segments_by_hash(
+  state: AppState,
+  Path(prefix): Path<String>,
+  Query(query): Query<SegmentQuery>,
+) -> AppResult<Response> {
+  let prefix = parse_hash_prefix(&prefix)?;
+  let filter = query.filter()?;
+  let network = state.config.voting.network();
+
+  let mut db_con = state.db_con().await?;
+  let videos = db::videos_with_hash_prefix(&mut db_con, &prefix)
+    .await
+    .with_context_into_app(|| format!("Failed to fetch videos for hash prefix {prefix}"))?;
+  let aids: Vec<i64> = videos.iter().map(|video| video.aid).collect();
+  let segments = db::segments_related_to_aids(&mut db_con, &aids, &filter, network)
+    .await
+    .with_context_into_app(|| format!("Failed to fetch segments for hash prefix {prefix}"))?;
+  let mut grouped: HashMap<i64, Vec<SbSegment>> = HashMap::with_capacity(videos.len());
+  for (aid, segment) in to_sb_segments(&mut db_con, segments).await? {
+    grouped.entry(aid).or_default().push(segment);
+  }
+
+  let sb_videos: Vec<SbVideo> = videos
+    .into_iter()
+    .filter_map(|video| {
+      Some(SbVideo {
+        segments: grouped.remove(&video.aid)?,
+        video_id: Abv::new(video.aid as u64)?.bv(),
+        hash: video.bvid_hash?,
+      })
+    })
+    .collect();
+
+  if sb_videos.is_empty() {
+    return Ok((StatusCode::NOT_FOUND, Json(sb_videos)).into_response());
+  }
+  Ok(Json(sb_videos).into_response())
+}
@@ -0,0 +1,90 @@
+use axum::extract::Query;
+use diesel::OptionalExtension;
+
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct UserInfoQuery {
+  /// Private secret
+  #[serde(rename = "userID")]
+  pub user_id: Option<Uuid>,
+  #[serde(rename = "publicUserID")]
+  pub public_user_id: Option<Uuid>,
+}
+
+/// Fields we don't track are always zero
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UserInfo {
+  /// Public id
+  #[serde(rename = "userID")]
+  pub user_id: Uuid,
+  pub user_name: String,
+  pub minutes_saved: f64,
+  pub segment_count: i64,
+  pub ignored_segment_count: i64,
+  pub view_count: i64,
+  pub ignored_view_count: i64,
+  pub warnings: i64,
+  pub reputation: f64,
+  pub vip: bool,
+  #[serde(rename = "lastSegmentID")]
+  pub last_segment_id: Option<Uuid>,
+}
+
+/// `GET /api/userInfo?userID=...`
+pub async fn user_info(
+  state: AppState,
+  Query(query): Query<UserInfoQuery>,
+) -> AppResult<Json<UserInfo>> {
+  let user_id = match (query.user_id, query.public_user_id) {
+    (Some(secret), _) => db::User::public_id(&secret),
+    (None, Some(public_id)) => public_id,
+    (None, None) => {
+      return Err(app_err_custom!(
+        StatusCode::BAD_REQUEST,
+        RespCode::INVALID_PARAMS,
+        "Either `userID` or `publicUserID` is required"
+      ))
+    },
+  };
+  let mut db_con = state.db_con().await?;
+
+  let user: Option<(db::UserRole, f32)> = db::users::table
+    .find(user_id)
+    .select((db::users::role, db::users::trust))
+    .first(&mut db_con)
+    .await
+    .optional()
+    .with_context_into_app(|| format!("Failed to fetch user {user_id}"))?;
+
+  let segment_count = db::segments::table
+    .filter(db::segments::submitter.eq(user_id))
+    .count()
+    .get_result::<i64>(&mut db_con)
+    .await
+    .with_context_into_app(|| format!("Failed to count segments of user {user_id}"))?;
+
+  let last_segment_id = db::segments::table
+    .filter(db::segments::submitter.eq(user_id))
+    .order(db::segments::time.desc())
+    .select(db::segments::id)
+    .first::<Uuid>(&mut db_con)
+    .await
+    .optional()
+    .with_context_into_app(|| format!("Failed to fetch last segment of user {user_id}"))?;
+
+  Ok(Json(UserInfo {
+    user_id,
+    user_name: user_id.to_string(),
+    minutes_saved: 0.0,
+    segment_count,
+    ignored_segment_count: 0,
+    view_count: 0,
+    ignored_view_count: 0,
+    warnings: 0,
+    reputation: user.map_or(0.0, |(_, trust)| trust as f64),
+    vip: user.is_some_and(|(role, _)| role >= db::UserRole::Vip),
+    last_segment_id,
+  }))
+}
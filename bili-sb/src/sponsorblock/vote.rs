@@ -0,0 +1,50 @@
+use axum::extract::Query;
+
+use super::*;
+
+#[derive(Deserialize, Debug)]
+pub struct VoteQuery {
+  #[serde(rename = "UUID")]
+  pub uuid: Uuid,
+  /// Private secret
+  #[serde(rename = "userID")]
+  pub user_id: Uuid,
+  /// `0` for downvote, `1` for upvote, `20` for undo, ignored for category votes
+  pub r#type: Option<u8>,
+  /// Category vote, an upvote suggesting the category
+  pub category: Option<db::SegmentCategory>,
+}
+
+/// `POST /api/voteOnSponsorTime?UUID=...&userID=...&type=1`, or `&category=...` for category votes
+pub async fn vote_on_sponsor_time(
+  state: AppState,
+  ip: SecureClientIp,
+  ip_shadow_banned: Option<Extension<IpShadowBanned>>,
+  Query(query): Query<VoteQuery>,
+) -> AppResult<StatusCode> {
+  let r#type = match (query.category, query.r#type) {
+    (Some(_), _) | (None, Some(1)) => VoteReqType::Up,
+    (None, Some(0)) => VoteReqType::Down,
+    (None, Some(20)) => VoteReqType::Undo,
+    (None, other) => {
+      return Err(app_err_custom!(
+        StatusCode::BAD_REQUEST,
+        RespCode::INVALID_PARAMS,
+        "Unsupported vote type {:?}",
+        other
+      ))
+    },
+  };
+
+  let req = SegmentVoteReq {
+    id: query.uuid,
+    voter: query.user_id,
+    r#type,
+    suggestion: query
+      .category
+      .map(|category| SuggestionReq::Category { category }),
+  };
+  segment_vote(state, ip, ip_shadow_banned, Json(req)).await?;
+
+  Ok(StatusCode::OK)
+}
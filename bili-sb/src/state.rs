@@ -5,18 +5,20 @@ use axum::extract::State;
 use dashmap::DashMap;
 use diesel_async::{
   pooled_connection::{AsyncDieselConnectionManager, PoolableConnection, RecyclingMethod},
+  scoped_futures::ScopedFutureExt,
   AsyncPgConnection,
 };
 use http::Uri;
 use log::info;
-use tokio::sync::OnceCell;
 use uuid::Uuid;
 
 use crate::{
-  client::{self, *},
+  client::Upstream,
   config::Config,
   data::RespCode,
+  db,
   error::*,
+  metadata::{RecordedViews, VideoFetches},
 };
 
 pub type AppState = State<Arc<App>>;
@@ -24,9 +26,11 @@ pub type ADashMap<K, V> = DashMap<K, V, BuildHasherDefault<ahash::AHasher>>;
 
 #[derive(Clone, Debug)]
 pub struct App {
-  bili_channel: OnceCell<tonic::transport::Channel>,
+  pub upstream: Arc<Upstream>,
   db_pool: PgAsyncPool,
   pub pow_map: Arc<ADashMap<Uuid, PowProperty>>,
+  pub video_fetches: Arc<VideoFetches>,
+  pub recorded_views: Arc<RecordedViews>,
   pub config: Arc<Config>,
 }
 
@@ -51,39 +55,48 @@ impl App {
       .with_context(|| format!("Failed to connect database, url: `{}`", database_url))?;
 
     // early check for database connection
-    pool
+    let mut con = pool
       .get()
       .await
-      .context("Unable to get a instance from connection pool")?
+      .context("Unable to get a instance from connection pool")?;
+    con
       .ping(&RecyclingMethod::Verified)
       .await
       .with_context(|| format!("Failed to ping database, url: `{}`", database_url))?;
 
+    let backfilled = db::backfill_video_hashes(&mut con)
+      .await
+      .context("Failed to backfill video hashes")?;
+    if backfilled != 0 {
+      info!("Backfilled bvid hash for {} videos", backfilled);
+    }
+
+    if !config.admins.is_empty() {
+      let admins = &config.admins;
+      let promoted = con
+        .build_transaction()
+        .run::<_, diesel::result::Error, _>(|con| {
+          async move { db::promote_admins(con, admins).await }.scope_boxed()
+        })
+        .await
+        .context("Failed to promote admins")?;
+      info!("Promoted {} of {} admins", promoted, config.admins.len());
+    }
+    drop(con);
+
+    let upstream = Upstream::new(&config.upstream).context("Failed to set up bilibili client")?;
+    info!("Bilibili endpoints: {:?}", config.upstream.endpoints);
+
     Ok(Self {
-      bili_channel: Default::default(),
+      upstream: Arc::new(upstream),
       db_pool: pool,
       pow_map: Arc::new(DashMap::default()),
+      video_fetches: Arc::new(DashMap::default()),
+      recorded_views: Arc::new(DashMap::default()),
       config,
     })
   }
 
-  #[allow(dead_code)]
-  pub async fn bili(&self) -> AppResult<tonic::transport::Channel> {
-    self
-      .bili_channel
-      .get_or_try_init(|| async {
-        info!(
-          "Connecting bilibili grpc server `{}`",
-          BILI_GRPC_FAILOVER_URL
-        );
-        client::connect(BILI_GRPC_FAILOVER_URL).await
-      })
-      .await
-      .cloned()
-      .with_app_error(RespCode::BILI_CLIENT_ERROR)
-      .into_app_result()
-  }
-
   pub async fn db_con(&self) -> AppResult<PooledPgCon<'_>> {
     self
       .db_pool
@@ -93,16 +106,6 @@ impl App {
       .with_app_error(RespCode::DATABASE_ERROR)
       .into_app_result()
   }
-
-  pub async fn db_con_owned(&self) -> AppResult<PooledPgCon<'static>> {
-    self
-      .db_pool
-      .get_owned()
-      .await
-      .context("Failed to get pooled database connection")
-      .with_app_error(RespCode::DATABASE_ERROR)
-      .into_app_result()
-  }
 }
 
 pub type PgAsyncPool = bb8::Pool<PgConnectionManager>;
@@ -0,0 +1,133 @@
+//! Per user trust score, computed from votes on their segments, account age and moderation
+//!
+//! It weights votes of the user, see [crate::config::VotingConfig::weight], and decides whether
+//! new segments of the user start as low confidence, see [crate::config::TrustConfig].
+
+use std::{
+  collections::HashMap,
+  time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::{db, state::App};
+
+pub const MIN_TRUST: f32 = -10.0;
+pub const MAX_TRUST: f32 = 10.0;
+
+const DAY: f32 = 24.0 * 60.0 * 60.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct TrustStats {
+  /// Counted votes on the user's segments
+  pub up_votes: i64,
+  pub down_votes: i64,
+  /// Segments hidden by moderators
+  pub hidden_segments: i64,
+  /// Segments hidden by votes
+  pub auto_hidden_segments: i64,
+  pub age: Duration,
+  pub banned: bool,
+}
+
+/// In [MIN_TRUST]..=[MAX_TRUST], a new account starts at 0
+pub fn trust_of(stats: &TrustStats) -> f32 {
+  if stats.banned {
+    return MIN_TRUST;
+  }
+  // every 5 net upvotes count 1, up to 5
+  let votes = ((stats.up_votes - stats.down_votes) as f32 / 5.0).clamp(-5.0, 5.0);
+  // 1 per month, up to 3
+  let age = (stats.age.as_secs_f32() / DAY / 30.0).min(3.0);
+  let moderation = stats.hidden_segments as f32 * 2.0 + stats.auto_hidden_segments as f32;
+  (votes + age - moderation).clamp(MIN_TRUST, MAX_TRUST)
+}
+
+/// Recomputes trust of every user, returns the number of updated users
+pub async fn refresh_trust(state: &App) -> anyhow::Result<usize> {
+  let mut db_con = state.db_con().await?;
+
+  let users: Vec<(Uuid, SystemTime, bool, bool, f32)> = db::users::table
+    .select((
+      db::users::id,
+      db::users::register_time,
+      db::users::banned,
+      db::users::shadow_banned,
+      db::users::trust,
+    ))
+    .load(&mut db_con)
+    .await
+    .context("Failed to fetch users")?;
+
+  let mut stats: HashMap<Uuid, TrustStats> = HashMap::with_capacity(users.len());
+  for (submitter, type_, count) in db::submitter_votes(&mut db_con)
+    .await
+    .context("Failed to fetch votes of submitters")?
+  {
+    let stats = stats.entry(submitter).or_default();
+    match type_ {
+      db::VoteType::Up => stats.up_votes = count,
+      db::VoteType::Down => stats.down_votes = count,
+    }
+  }
+  for (submitter, hidden, auto_hidden, count) in db::submitter_hidden(&mut db_con)
+    .await
+    .context("Failed to fetch hidden segments of submitters")?
+  {
+    let stats = stats.entry(submitter).or_default();
+    if hidden {
+      stats.hidden_segments += count;
+    } else if auto_hidden {
+      stats.auto_hidden_segments += count;
+    }
+  }
+
+  let now = SystemTime::now();
+  let mut updated = 0;
+  for (id, register_time, banned, shadow_banned, old_trust) in users {
+    let mut stats = stats.remove(&id).unwrap_or_default();
+    stats.age = now.duration_since(register_time).unwrap_or_default();
+    stats.banned = banned || shadow_banned;
+
+    let trust = trust_of(&stats);
+    if (trust - old_trust).abs() < 0.01 {
+      continue;
+    }
+    diesel::update(db::users::table.find(id))
+      .set(db::users::trust.eq(trust))
+      .execute(&mut db_con)
+      .await
+      .with_context(|| format!("Failed to update trust of user {id}"))?;
+    updated += 1;
+  }
+
+  Ok(updated)
+}
+
+#[test]
+fn trust_test() {
+  const MONTH: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+  assert_eq!(trust_of(&TrustStats::default()), 0.0);
+  let veteran = TrustStats {
+    up_votes: 100,
+    age: MONTH * 12,
+    ..Default::default()
+  };
+  assert_eq!(trust_of(&veteran), 8.0);
+  let spammer = TrustStats {
+    down_votes: 10,
+    auto_hidden_segments: 3,
+    age: MONTH,
+    ..Default::default()
+  };
+  assert_eq!(trust_of(&spammer), -4.0);
+  let banned = TrustStats {
+    banned: true,
+    ..veteran
+  };
+  assert_eq!(trust_of(&banned), MIN_TRUST);
+}